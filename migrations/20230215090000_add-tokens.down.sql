@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop table revoked_token;
+drop table refresh_token;
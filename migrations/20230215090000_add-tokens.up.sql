@@ -0,0 +1,25 @@
+-- Add up migration script here
+-- refresh_token, stored as a hash of the opaque token sent to clients
+CREATE TABLE IF NOT EXISTS refresh_token (
+  id INT NOT NULL AUTO_INCREMENT,
+  user_id INT NOT NULL,
+  token_hash CHAR(64) NOT NULL,
+  family CHAR(32) NOT NULL,
+  expires_at DATETIME NOT NULL,
+  revoked_at DATETIME,
+  replaced_by INT,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `refresh_token_hash` (`token_hash`),
+  KEY `refresh_token_family` (`family`),
+  CONSTRAINT `refresh_token_user_id` FOREIGN KEY (`user_id`) REFERENCES `user` (`id`) ON DELETE CASCADE
+);
+
+-- revoked_token, access tokens revoked before expiry, keyed by jwt `jti`
+CREATE TABLE IF NOT EXISTS revoked_token (
+  jti CHAR(32) NOT NULL,
+  expires_at DATETIME NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`jti`),
+  KEY `revoked_token_expires_at` (`expires_at`)
+);
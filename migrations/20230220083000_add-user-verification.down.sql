@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE user DROP COLUMN verify_sent_at;
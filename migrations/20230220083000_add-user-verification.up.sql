@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE user ADD COLUMN verify_sent_at DATETIME;
+
+-- accounts created before email verification existed were never activated, only
+-- verified accounts may log in now so they count as verified
+UPDATE user SET is_active = 1 WHERE deleted_at IS NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop table password_reset;
+ALTER TABLE user DROP COLUMN password_changed_at;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- access tokens issued before this time are rejected
+ALTER TABLE user ADD COLUMN password_changed_at DATETIME;
+
+-- password_reset, single-use tokens sent by mail, stored as hash
+CREATE TABLE IF NOT EXISTS password_reset (
+  id INT NOT NULL AUTO_INCREMENT,
+  user_id INT NOT NULL,
+  token_hash CHAR(64) NOT NULL,
+  expires_at DATETIME NOT NULL,
+  used_at DATETIME,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `password_reset_hash` (`token_hash`),
+  CONSTRAINT `password_reset_user_id` FOREIGN KEY (`user_id`) REFERENCES `user` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE article_tag DROP FOREIGN KEY `at_article_id`;
+ALTER TABLE article_tag DROP FOREIGN KEY `at_tag_id`;
+ALTER TABLE article_tag DROP INDEX `article_tag_unique`;
+ALTER TABLE article_tag
+  ADD CONSTRAINT `at_article_id` FOREIGN KEY (`article_id`) REFERENCES `article` (`id`),
+  ADD CONSTRAINT `at_tag_id` FOREIGN KEY (`tag_id`) REFERENCES `tag` (`id`);
@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- drop duplicated links before the unique key is added
+DELETE a FROM article_tag a
+  INNER JOIN article_tag b ON a.article_id = b.article_id AND a.tag_id = b.tag_id AND a.id > b.id;
+
+-- links go away together with their article or tag
+ALTER TABLE article_tag DROP FOREIGN KEY `at_article_id`;
+ALTER TABLE article_tag DROP FOREIGN KEY `at_tag_id`;
+ALTER TABLE article_tag
+  ADD UNIQUE KEY `article_tag_unique` (`article_id`, `tag_id`),
+  ADD CONSTRAINT `at_article_id` FOREIGN KEY (`article_id`) REFERENCES `article` (`id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `at_tag_id` FOREIGN KEY (`tag_id`) REFERENCES `tag` (`id`) ON DELETE CASCADE;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+-- the statuses articles had before the up migration published them are not restored
+ALTER TABLE article DROP INDEX `article_status_published_at`;
+ALTER TABLE article DROP COLUMN published_at;
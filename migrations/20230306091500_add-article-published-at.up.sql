@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- status: 0 draft, 1 pending review, 2 published, 3 scheduled, 4 archived
+-- the status was never enforced before, every article was listed publicly, so all the
+-- existing articles stay visible as published ones
+UPDATE article SET status = 2;
+
+-- publish time of published articles, or the planned one of scheduled articles
+ALTER TABLE article ADD COLUMN published_at DATETIME AFTER is_top;
+UPDATE article SET published_at = created_at WHERE status = 2;
+ALTER TABLE article ADD KEY `article_status_published_at` (`status`, `published_at`);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE article DROP INDEX `article_status_unpublish_at`;
+ALTER TABLE article DROP COLUMN unpublish_at;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- published articles are archived by the worker once this time is reached
+ALTER TABLE article ADD COLUMN unpublish_at DATETIME AFTER published_at;
+ALTER TABLE article ADD KEY `article_status_unpublish_at` (`status`, `unpublish_at`);
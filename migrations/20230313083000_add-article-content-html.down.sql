@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE article DROP COLUMN content_html;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- sanitized html rendered from the markdown content, articles saved before are rendered on read
+ALTER TABLE article ADD COLUMN content_html MEDIUMTEXT AFTER content;
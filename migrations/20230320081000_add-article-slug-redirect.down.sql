@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table slug_redirect;
+ALTER TABLE article
+  DROP INDEX `article_slug`,
+  MODIFY slug VARCHAR(128);
@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- every article gets a unique slug
+UPDATE article SET slug = CONCAT('article-', id) WHERE slug IS NULL OR slug = '';
+UPDATE article a INNER JOIN article b ON a.slug = b.slug AND a.id > b.id
+  SET a.slug = CONCAT(LEFT(a.slug, 100), '-', a.id);
+ALTER TABLE article
+  MODIFY slug VARCHAR(128) NOT NULL,
+  ADD UNIQUE KEY `article_slug` (`slug`);
+
+-- slug_redirect, former slugs of renamed articles
+CREATE TABLE IF NOT EXISTS slug_redirect (
+  id INT NOT NULL AUTO_INCREMENT,
+  slug VARCHAR(128) NOT NULL,
+  article_id INT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `slug_redirect_slug` (`slug`),
+  CONSTRAINT `slug_redirect_article_id` FOREIGN KEY (`article_id`) REFERENCES `article` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- article_revision, a snapshot of the article written on every save. The editor is
+-- forgotten when their account is purged, the history of the article stays.
+CREATE TABLE IF NOT EXISTS article_revision (
+  id INT NOT NULL AUTO_INCREMENT,
+  article_id INT NOT NULL,
+  user_id INT,
+  title VARCHAR(256) NOT NULL,
+  content TEXT NOT NULL,
+  summary VARCHAR(256),
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  KEY `article_revision_article_id` (`article_id`, `id`),
+  CONSTRAINT `article_revision_article_id` FOREIGN KEY (`article_id`) REFERENCES `article` (`id`) ON DELETE CASCADE,
+  CONSTRAINT `article_revision_user_id` FOREIGN KEY (`user_id`) REFERENCES `user` (`id`) ON DELETE SET NULL
+);
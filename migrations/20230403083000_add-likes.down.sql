@@ -0,0 +1,4 @@
+-- Add down migration script here
+drop table reply_like;
+drop table comment_like;
+drop table article_like;
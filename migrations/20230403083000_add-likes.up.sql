@@ -0,0 +1,35 @@
+-- Add up migration script here
+-- article_like, comment_like and reply_like, one row per user and liked item,
+-- `like_count` of the item is kept in step with them
+CREATE TABLE IF NOT EXISTS article_like (
+  id INT NOT NULL AUTO_INCREMENT,
+  article_id INT NOT NULL,
+  user_id INT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `article_like_article_user` (`article_id`, `user_id`),
+  CONSTRAINT `article_like_article_id` FOREIGN KEY (`article_id`) REFERENCES `article` (`id`) ON DELETE CASCADE,
+  CONSTRAINT `article_like_user_id` FOREIGN KEY (`user_id`) REFERENCES `user` (`id`) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS comment_like (
+  id INT NOT NULL AUTO_INCREMENT,
+  comment_id INT NOT NULL,
+  user_id INT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `comment_like_comment_user` (`comment_id`, `user_id`),
+  CONSTRAINT `comment_like_comment_id` FOREIGN KEY (`comment_id`) REFERENCES `comment` (`id`) ON DELETE CASCADE,
+  CONSTRAINT `comment_like_user_id` FOREIGN KEY (`user_id`) REFERENCES `user` (`id`) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS reply_like (
+  id INT NOT NULL AUTO_INCREMENT,
+  reply_id INT NOT NULL,
+  user_id INT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `reply_like_reply_user` (`reply_id`, `user_id`),
+  CONSTRAINT `reply_like_reply_id` FOREIGN KEY (`reply_id`) REFERENCES `reply` (`id`) ON DELETE CASCADE,
+  CONSTRAINT `reply_like_user_id` FOREIGN KEY (`user_id`) REFERENCES `user` (`id`) ON DELETE CASCADE
+);
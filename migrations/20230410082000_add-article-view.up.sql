@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- article_view, views of an article per day, `article.read_count` holds the total
+CREATE TABLE IF NOT EXISTS article_view (
+  id INT NOT NULL AUTO_INCREMENT,
+  article_id INT NOT NULL,
+  date DATE NOT NULL,
+  views INT NOT NULL DEFAULT 0,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `article_view_article_date` (`article_id`, `date`),
+  CONSTRAINT `article_view_article_id` FOREIGN KEY (`article_id`) REFERENCES `article` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+-- hashed passwords can not be turned back into clear text, those articles stay protected
+-- by a password nobody knows until their authors set a new one
+UPDATE article SET password_hash = SUBSTRING(password_hash, 7) WHERE password_hash LIKE 'plain:%';
+UPDATE article SET password_hash = LEFT(password_hash, 32) WHERE CHAR_LENGTH(password_hash) > 32;
+ALTER TABLE article CHANGE password_hash password VARCHAR(32);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE article CHANGE password password_hash VARCHAR(256);
+-- passwords were stored in clear text, they are marked here and hashed when the server starts
+UPDATE article SET password_hash = CONCAT('plain:', password_hash) WHERE password_hash IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE category DROP FOREIGN KEY `category_parent_id`;
+ALTER TABLE category DROP COLUMN parent_id;
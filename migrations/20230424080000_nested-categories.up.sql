@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- categories form a tree, top-level categories have no parent
+ALTER TABLE category
+  ADD COLUMN parent_id INT AFTER description,
+  ADD CONSTRAINT `category_parent_id` FOREIGN KEY (`parent_id`) REFERENCES `category` (`id`);
@@ -1,38 +1,96 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, Query, State},
-    routing::get,
-    Json, Router,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Router,
 };
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
 use serde_json::Value;
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors};
 
-use super::{ApiResponse, Pagination};
+use super::{
+    comment,
+    extract::{Json, Path, Query},
+    revision, ApiResponse, Pagination,
+};
 use crate::{
     errors::{AppResult, Error},
-    models::article::{Article, CreateArticle, UpdateArticle},
+    models::{
+        article::{
+            Article, ArticleDetail, ArticleFilter, ArticleStatus, ArticleStatusData, CreateArticle,
+            PreviewArticle, PublicArticle, UnlockArticle, UpdateArticle, Visibility,
+        },
+        article_view::{ArticleView, DailyViews, ViewRange},
+        like::{Like, LikeTarget},
+        role::{Permission, Role},
+        slug_redirect::SlugRedirect,
+    },
     router::AppState,
-    utils::jwt::Claims,
+    utils::{
+        client::Client,
+        hash::verify_password,
+        jwt::{self, Claims},
+        markdown,
+        permission::{RequirePermission, Write},
+    },
 };
 
+// header carrying the token returned by `POST /:id/unlock`
+const ARTICLE_TOKEN_HEADER: &str = "x-article-token";
+
 pub fn create_route() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_articles).post(create_article))
+        .route("/preview", post(preview_article))
+        .route("/slug/:slug", get(get_article_by_slug))
+        .route("/trash", get(get_trashed_articles))
         .route(
             "/:id",
             get(get_article).put(update_article).delete(delete_article),
         )
+        .route("/:id/status", put(update_article_status))
+        .route("/:id/restore", post(restore_article))
+        .route("/:id/like", post(like_article).delete(unlike_article))
+        .route("/:id/unlock", post(unlock_article))
+        .route("/:id/views", get(get_article_views))
+        .nest("/:id/comments", comment::create_route())
+        .nest("/:id/revisions", revision::create_route())
 }
 
 // 注册新标签
+#[utoipa::path(
+    post,
+    path = "/api/articles",
+    tag = "articles",
+    responses(
+        (status = 200, description = "The new article", body = ApiArticle),
+        (status = 400, description = "Forbidden status change or invalid dates", body = ErrorResponse),
+        (status = 403, description = "Writers only", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn create_article(
-    claims: Claims,
+    claims: RequirePermission<Write>,
     State(state): State<Arc<AppState>>,
     Json(article_info): Json<CreateArticle>,
 ) -> AppResult<Json<Value>> {
     let user_id = claims.user.id;
-    let uid = Article::create(&state.pool, user_id, &article_info).await?;
-    let new_article = Article::find_by_id(&state.pool, uid as i32).await?;
+    let published_at = check_transition(
+        &claims,
+        None,
+        article_info.status,
+        article_info.published_at,
+    )?;
+    check_unpublish_at(article_info.status, published_at, article_info.unpublish_at)?;
+    let uid = Article::create(&state.pool, user_id, &article_info, published_at).await?;
+    reindex(&state, &[uid as i32]).await;
+    let new_article = Article::find_detail(&state.pool, uid as i32, Some(user_id)).await?;
     if new_article.is_none() {
         return Err(Error::NotFound(String::from("article")));
     }
@@ -42,53 +100,240 @@ pub async fn create_article(
     Ok(Json(serde_json::json!(resp)))
 }
 
+// 预览文章内容渲染结果，不保存
+#[utoipa::path(
+    post,
+    path = "/api/articles/preview",
+    tag = "articles",
+    responses(
+        (status = 200, description = "The rendered html", body = ApiPreview),
+        (status = 403, description = "Writers only", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn preview_article(
+    _perm: RequirePermission<Write>,
+    Json(preview_info): Json<PreviewArticle>,
+) -> AppResult<Json<Value>> {
+    let content_html = markdown::render(&preview_info.content);
+
+    let resp = ApiResponse::new(Preview { content_html });
+    Ok(Json(serde_json::json!(resp)))
+}
+
 // 获取标签列表
+#[utoipa::path(
+    get,
+    path = "/api/articles",
+    tag = "articles",
+    params(Pagination, ArticleFilter),
+    responses(
+        (status = 200, description = "Articles the caller can see, protected ones locked", body = ApiArticlePage),
+    ),
+    security((), ("bearer" = []))
+)]
 pub async fn get_articles(
+    claims: Option<Claims>,
     State(state): State<Arc<AppState>>,
     Query(pagination): Query<Pagination>,
+    Query(mut filter): Query<ArticleFilter>,
 ) -> AppResult<Json<Value>> {
-    let articles = Article::find_list(&state.pool, &pagination).await?;
+    filter.visibility = visibility(&state, claims.as_ref()).await?;
+    let viewer = claims.as_ref().map(|c| c.user.id);
+    let mut articles = Article::find_list(&state.pool, &filter, viewer, &pagination).await?;
+    lock_protected(filter.visibility, &mut articles.list);
 
     let resp = ApiResponse::new(articles);
     Ok(Json(serde_json::json!(resp)))
 }
 
-// 获取指定标签
+// 获取指定标签，受密码保护的文章需要解锁令牌才返回内容
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}",
+    tag = "articles",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The article with its outline, locked without a valid token", body = ApiArticle),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
 pub async fn get_article(
+    claims: Option<Claims>,
+    client: Client,
+    headers: HeaderMap,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
 ) -> AppResult<Json<Value>> {
-    let article = Article::find_by_id(&state.pool, id).await?;
-    if article.is_none() {
+    let viewer = claims.as_ref().map(|c| c.user.id);
+    let article = Article::find_detail(&state.pool, id, viewer).await?;
+    let visibility = visibility(&state, claims.as_ref()).await?;
+    // unpublished articles are hidden as if they did not exist
+    if article.is_none() || !visibility.can_see(&article.as_ref().unwrap().article) {
         return Err(Error::NotFound(String::from("article")));
     }
 
-    let article = article.unwrap();
+    let mut article = article.unwrap();
+    if !visibility.owns(&article.article) && !is_unlocked(&state, &headers, &article.article) {
+        article.lock();
+    }
+    // only readers of published articles count, not authors checking their drafts
+    if article.article.status == ArticleStatus::Published {
+        state.views.record(id, client.viewer_key(viewer));
+    }
     let resp = ApiResponse::new(article);
     Ok(Json(serde_json::json!(resp)))
 }
 
+// 通过 slug 获取文章，旧的 slug 永久重定向到新地址
+#[utoipa::path(
+    get,
+    path = "/api/articles/slug/{slug}",
+    tag = "articles",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The article with its outline, locked without a valid token", body = ApiArticle),
+        (status = 301, description = "A former slug, redirecting to the current one"),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn get_article_by_slug(
+    claims: Option<Claims>,
+    client: Client,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> AppResult<Response> {
+    let id = match Article::find_id_by_slug(&state.pool, &slug).await? {
+        Some(id) => id,
+        None => {
+            let target = SlugRedirect::find_target(&state.pool, &slug).await?;
+            if target.is_none() {
+                return Err(Error::NotFound(String::from("article")));
+            }
+            let location = format!("/api/articles/slug/{}", target.unwrap());
+            return Ok((
+                StatusCode::MOVED_PERMANENTLY,
+                [(header::LOCATION, location)],
+            )
+                .into_response());
+        }
+    };
+
+    get_article(claims, client, headers, State(state), Path(id))
+        .await
+        .map(IntoResponse::into_response)
+}
+
 // 更新指定标签的信息
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}",
+    tag = "articles",
+    responses(
+        (status = 200, description = "The updated article", body = ApiArticle),
+        (status = 403, description = "Neither the author nor a moderator", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_article(
-    claims: Claims,
+    claims: RequirePermission<Write>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
     Json(article_info): Json<UpdateArticle>,
 ) -> AppResult<Json<Value>> {
     let article = Article::find_by_id(&state.pool, id).await?;
-    if article.is_none() || article.unwrap().user_id != claims.user.id {
+    if article.is_none() {
         return Err(Error::NotFound(String::from("article")));
     }
+    if article.unwrap().user_id != claims.user.id && !claims.has_permission(Permission::Moderate) {
+        return Err(Error::Forbidden);
+    }
 
-    let update_ok = Article::update(&state.pool, id, &article_info).await?;
+    let update_ok = Article::update(
+        &state.pool,
+        id,
+        claims.user.id,
+        &article_info,
+        state.revision_retention,
+    )
+    .await?;
     if !update_ok {
         return Err(Error::NotFound(String::from("article")));
     }
+    reindex(&state, &[id]).await;
 
+    let article = Article::find_detail(&state.pool, id, Some(claims.user.id)).await?;
+    if article.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+
+    let article = article.unwrap();
+    let resp = ApiResponse::new(article);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 变更文章的发布状态
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}/status",
+    tag = "articles",
+    responses(
+        (status = 200, description = "The article in its new status", body = ApiArticle),
+        (status = 400, description = "Forbidden status change or invalid dates", body = ErrorResponse),
+        (status = 403, description = "Neither the author nor a moderator, or a pending article moved by a non-moderator", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_article_status(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(status_info): Json<ArticleStatusData>,
+) -> AppResult<Json<Value>> {
     let article = Article::find_by_id(&state.pool, id).await?;
     if article.is_none() {
         return Err(Error::NotFound(String::from("article")));
     }
+    let article = article.unwrap();
+    if article.user_id != claims.user.id && !claims.has_permission(Permission::Moderate) {
+        return Err(Error::Forbidden);
+    }
+
+    let published_at = check_transition(
+        &claims,
+        Some(&article),
+        status_info.status,
+        status_info.published_at,
+    )?;
+    check_unpublish_at(status_info.status, published_at, status_info.unpublish_at)?;
+    let update_ok = Article::update_status(
+        &state.pool,
+        id,
+        status_info.status,
+        published_at,
+        status_info.unpublish_at,
+    )
+    .await?;
+    if !update_ok {
+        return Err(Error::NotFound(String::from("article")));
+    }
+    reindex(&state, &[id]).await;
+
+    let article = Article::find_detail(&state.pool, id, Some(claims.user.id)).await?;
+    if article.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
 
     let article = article.unwrap();
     let resp = ApiResponse::new(article);
@@ -96,17 +341,431 @@ pub async fn update_article(
 }
 
 // 删除指定标签
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}",
+    tag = "articles",
+    responses(
+        (status = 200, description = "Moved to the trash", body = ApiEmpty),
+        (status = 403, description = "Neither the author nor a moderator", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_article(
-    claims: Claims,
+    claims: RequirePermission<Write>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
 ) -> AppResult<Json<Value>> {
     let article = Article::find_by_id(&state.pool, id).await?;
-    if article.is_none() || article.unwrap().user_id != claims.user.id {
+    if article.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+    if article.unwrap().user_id != claims.user.id && !claims.has_permission(Permission::Moderate) {
+        return Err(Error::Forbidden);
+    }
+
+    if !Article::delete(&state.pool, id).await? {
         return Err(Error::NotFound(String::from("article")));
     }
+    reindex(&state, &[id]).await;
 
-    Article::delete(&state.pool, id).await?;
     let resp = ApiResponse::new(());
     Ok(Json(serde_json::json!(resp)))
 }
+
+// 获取回收站中的文章列表，作者只能看到自己的文章
+#[utoipa::path(
+    get,
+    path = "/api/articles/trash",
+    tag = "articles",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Deleted articles, latest deleted first", body = ApiTrashedArticlePage),
+        (status = 403, description = "Writers only", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_trashed_articles(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Query(pagination): Query<Pagination>,
+) -> AppResult<Json<Value>> {
+    let articles = Article::find_trash(&state.pool, trash_owner(&claims), &pagination).await?;
+
+    let resp = ApiResponse::new(articles);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 从回收站恢复指定文章
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/restore",
+    tag = "articles",
+    responses(
+        (status = 200, description = "The restored article", body = ApiArticle),
+        (status = 403, description = "Writers only", body = ErrorResponse),
+        (status = 404, description = "No such article of the caller in the trash", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_article(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> AppResult<Json<Value>> {
+    if !Article::restore(&state.pool, id, trash_owner(&claims)).await? {
+        return Err(Error::NotFound(String::from("article")));
+    }
+    reindex(&state, &[id]).await;
+
+    let article = Article::find_detail(&state.pool, id, Some(claims.user.id)).await?;
+    if article.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+
+    let article = article.unwrap();
+    let resp = ApiResponse::new(article);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 点赞指定文章
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/like",
+    tag = "articles",
+    responses(
+        (status = 200, description = "Liked, liking twice counts once", body = ApiLikeState),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn like_article(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> AppResult<Json<Value>> {
+    find_visible(&state, Some(&claims), id).await?;
+    let like_count = Like::add(&state.pool, LikeTarget::Article, id, claims.user.id).await?;
+    if like_count.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+
+    let resp = ApiResponse::new(LikeState {
+        liked: true,
+        like_count: like_count.unwrap(),
+    });
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 取消点赞指定文章
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/like",
+    tag = "articles",
+    responses(
+        (status = 200, description = "Not liked anymore", body = ApiLikeState),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn unlike_article(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> AppResult<Json<Value>> {
+    find_visible(&state, Some(&claims), id).await?;
+    let like_count = Like::remove(&state.pool, LikeTarget::Article, id, claims.user.id).await?;
+    if like_count.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+
+    let resp = ApiResponse::new(LikeState {
+        liked: false,
+        like_count: like_count.unwrap(),
+    });
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 输入密码解锁受保护的文章，返回只对该文章有效的短期令牌
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/unlock",
+    tag = "articles",
+    responses(
+        (status = 200, description = "A token to send in the `x-article-token` header", body = ApiUnlockToken),
+        (status = 400, description = "The article is not password protected", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+        (status = 422, description = "Wrong password", body = ErrorResponse),
+        (status = 429, description = "Too many wrong passwords", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn unlock_article(
+    claims: Option<Claims>,
+    client: Client,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(payload): Json<UnlockArticle>,
+) -> AppResult<Json<Value>> {
+    let article = find_visible(&state, claims.as_ref(), id).await?;
+    let password_hash = match article.password_hash {
+        Some(password_hash) => password_hash,
+        None => {
+            return Err(Error::BadRequest(String::from(
+                "article is not password protected",
+            )))
+        }
+    };
+
+    let key = (id, client.ip);
+    if state.unlock_throttle.is_blocked(&key) {
+        return Err(Error::TooManyRequests(String::from(
+            "too many wrong passwords, please try again later",
+        )));
+    }
+    if !verify_password(&payload.password, &password_hash)? {
+        state.unlock_throttle.fail(key);
+        let mut error = ValidationError::new("password");
+        error.message = Some("is incorrect".into());
+        let mut errors = ValidationErrors::new();
+        errors.add("password", error);
+        return Err(Error::Validation(errors));
+    }
+    state.unlock_throttle.reset(&key);
+
+    let token = jwt::encode_unlock(id, &password_hash, &state.secret, state.unlock_token_ttl)?;
+    let expires_at = (chrono::Utc::now() + state.unlock_token_ttl).naive_utc();
+
+    let resp = ApiResponse::new(UnlockToken { token, expires_at });
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 获取文章每天的阅读量，默认为最近 30 天
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/views",
+    tag = "articles",
+    params(ViewRange),
+    responses(
+        (status = 200, description = "Daily views, of the last 30 days by default", body = ApiArticleViews),
+        (status = 400, description = "`from` is after `to`", body = ErrorResponse),
+        (status = 403, description = "Neither the author nor a moderator", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_article_views(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Query(range): Query<ViewRange>,
+) -> AppResult<Json<Value>> {
+    let article = Article::find_by_id(&state.pool, id).await?;
+    if article.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+    let article = article.unwrap();
+    if article.user_id != claims.user.id && !claims.has_permission(Permission::Moderate) {
+        return Err(Error::Forbidden);
+    }
+
+    let to = range.to.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let from = range.from.unwrap_or(to - chrono::Duration::days(29));
+    if from > to {
+        return Err(Error::BadRequest(String::from("from must not be after to")));
+    }
+    let days = ArticleView::find_daily(&state.pool, id, from, to).await?;
+
+    let resp = ApiResponse::new(ArticleViews {
+        read_count: article.read_count,
+        from,
+        to,
+        days,
+    });
+    Ok(Json(serde_json::json!(resp)))
+}
+
+/// Only articles the caller can read may be liked or unlocked
+async fn find_visible(
+    state: &AppState,
+    claims: Option<&Claims>,
+    id: i32,
+) -> AppResult<PublicArticle> {
+    let visibility = visibility(state, claims).await?;
+    match Article::find_by_id(&state.pool, id).await? {
+        Some(article) if visibility.can_see(&article) => Ok(article),
+        _ => Err(Error::NotFound(String::from("article"))),
+    }
+}
+
+/// Only articles the caller can read may have their comments read or written, protected
+/// ones have to be unlocked first unless the caller owns them
+pub async fn find_unlocked(
+    state: &AppState,
+    claims: Option<&Claims>,
+    headers: &HeaderMap,
+    id: i32,
+) -> AppResult<PublicArticle> {
+    let article = find_visible(state, claims, id).await?;
+    if !is_unlocked(state, headers, &article) && !visibility(state, claims).await?.owns(&article) {
+        return Err(Error::Forbidden);
+    }
+    Ok(article)
+}
+
+/// Whether the request carries a token unlocking the article with its current password
+fn is_unlocked(state: &AppState, headers: &HeaderMap, article: &PublicArticle) -> bool {
+    let password_hash = match &article.password_hash {
+        Some(password_hash) => password_hash,
+        None => return true,
+    };
+
+    headers
+        .get(ARTICLE_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|token| {
+            jwt::decode_unlock(token, article.id, password_hash, &state.secret).is_ok()
+        })
+}
+
+/// Withhold the content of protected articles in a list, except from their authors and
+/// moderators. Lists never take unlock tokens, those are scoped to a single article.
+pub fn lock_protected(visibility: Visibility, articles: &mut [ArticleDetail]) {
+    for article in articles {
+        if !visibility.owns(&article.article) {
+            article.lock();
+        }
+    }
+}
+
+/// Apply a change of the articles to the search index right away. A failure is only
+/// logged, the periodic sync of the index catches up with edited articles.
+pub async fn reindex(state: &AppState, ids: &[i32]) {
+    if let Err(e) = state.search.refresh(&state.pool, ids).await {
+        tracing::error!("update search index of articles {:?} failed: {}", ids, e);
+    }
+}
+
+/// Moderators manage the whole trash, authors only their own articles
+fn trash_owner(claims: &RequirePermission<Write>) -> Option<i32> {
+    if claims.has_permission(Permission::Moderate) {
+        None
+    } else {
+        Some(claims.user.id)
+    }
+}
+
+/// Which unpublished articles the caller may see: moderators see all of them,
+/// authors their own ones and anonymous readers none.
+pub async fn visibility(state: &AppState, claims: Option<&Claims>) -> AppResult<Visibility> {
+    let claims = match claims {
+        Some(claims) => claims,
+        None => return Ok(Visibility::Public),
+    };
+
+    let role = Role::find_by_user_id(&state.pool, claims.user.id).await?;
+    if role.is_some_and(|r| r.has_permission(Permission::Moderate)) {
+        Ok(Visibility::All)
+    } else {
+        Ok(Visibility::Author(claims.user.id))
+    }
+}
+
+/// Check that the caller may move `article` (a new one when `None`) to status `to`,
+/// returning the `published_at` to store along with it.
+fn check_transition(
+    claims: &RequirePermission<Write>,
+    article: Option<&PublicArticle>,
+    to: ArticleStatus,
+    published_at: Option<NaiveDateTime>,
+) -> AppResult<Option<NaiveDateTime>> {
+    let from = article.map_or(ArticleStatus::Draft, |a| a.status);
+    // a new article may simply stay a draft
+    let is_new_draft = article.is_none() && to == ArticleStatus::Draft;
+    if !is_new_draft && !from.can_transition_to(to) {
+        return Err(Error::BadRequest(format!(
+            "can not change status from {} to {}",
+            from.as_str(),
+            to.as_str()
+        )));
+    }
+
+    // approving or rejecting a post submitted for review is up to moderators
+    if !is_new_draft && !from.can_be_moved_to(to, claims.has_permission(Permission::Moderate)) {
+        return Err(Error::Forbidden);
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let previous = article.and_then(|a| a.published_at);
+    let published_at = match to {
+        // an archived article keeps its original date when published again
+        ArticleStatus::Published if from == ArticleStatus::Archived => previous.or(Some(now)),
+        ArticleStatus::Published => Some(now),
+        ArticleStatus::Scheduled => match published_at {
+            Some(at) if at > now => Some(at),
+            _ => {
+                return Err(Error::BadRequest(String::from(
+                    "published_at must be a future time when scheduling",
+                )))
+            }
+        },
+        ArticleStatus::Archived => previous,
+        ArticleStatus::Draft | ArticleStatus::Pending => None,
+    };
+
+    Ok(published_at)
+}
+
+/// An expiry time only makes sense for articles going live, and after they do
+fn check_unpublish_at(
+    to: ArticleStatus,
+    published_at: Option<NaiveDateTime>,
+    unpublish_at: Option<NaiveDateTime>,
+) -> AppResult<()> {
+    let unpublish_at = match unpublish_at {
+        Some(at) => at,
+        None => return Ok(()),
+    };
+
+    if !matches!(to, ArticleStatus::Published | ArticleStatus::Scheduled) {
+        return Err(Error::BadRequest(String::from(
+            "unpublish_at can only be set on published or scheduled articles",
+        )));
+    }
+    let now = chrono::Utc::now().naive_utc();
+    if unpublish_at <= published_at.unwrap_or(now).max(now) {
+        return Err(Error::BadRequest(String::from(
+            "unpublish_at must be a future time after published_at",
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Preview {
+    pub content_html: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LikeState {
+    pub liked: bool,
+    pub like_count: i32,
+}
+
+/// Sent back in the `x-article-token` header to read the unlocked article
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnlockToken {
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Views of an article per day between `from` and `to`, days without views are left out
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ArticleViews {
+    pub read_count: i32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub days: Vec<DailyViews>,
+}
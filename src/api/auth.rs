@@ -1,22 +1,62 @@
 use std::sync::Arc;
 
-use axum::{extract::State, routing::post, Json, Router};
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Router,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 use crate::{
     errors::{AppResult, AuthError, Error},
-    models::user::User,
+    mailer::Mail,
+    models::{
+        token::{PasswordReset, RefreshToken, RevokedToken},
+        user::{PublicUser, User},
+    },
     router::AppState,
-    utils::{hash::verify_password, jwt},
+    utils::{
+        hash::{generate_token, hash_token, verify_password},
+        jwt::{self, Claims},
+    },
+};
+
+use super::{
+    extract::{Json, Query},
+    ApiResponse,
 };
 
-use super::ApiResponse;
+const VERIFY_ACTION: &str = "verify";
+// minimum interval between two verification mails of the same user, in seconds
+const VERIFY_RESEND_INTERVAL: i64 = 60;
+// minimum interval between two password reset mails of the same user, in seconds
+const RESET_RESEND_INTERVAL: i64 = 60;
 
 pub fn create_route() -> Router<Arc<AppState>> {
-    Router::new().route("/", post(authorize))
+    Router::new()
+        .route("/", post(authorize))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/verify", get(verify))
+        .route("/verify/resend", post(resend_verification))
+        .route("/password/forgot", post(forgot_password))
+        .route("/password/reset", post(reset_password))
 }
 
+// 使用用户名或邮箱登录
+#[utoipa::path(
+    post,
+    path = "/api/auth",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Logged in", body = ApiAuth),
+        (status = 401, description = "Wrong credentials", body = ErrorResponse),
+        (status = 403, description = "The email address is not verified", body = ErrorResponse),
+    )
+)]
 async fn authorize(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<AuthPayload>,
@@ -34,23 +74,335 @@ async fn authorize(
     if !verify_password(&payload.password, &user.password_hash)? {
         return Err(Error::Auth(AuthError::WrongCredentials));
     }
+    if !user.is_active {
+        return Err(Error::InactiveAccount);
+    }
 
-    let token = jwt::encode(user, &state.secret)?;
+    // every login starts a new refresh token family
+    let refresh_token = generate_token(32);
+    RefreshToken::create(
+        &state.pool,
+        user.id,
+        &hash_token(&refresh_token),
+        &generate_token(16),
+        refresh_token_expires_at(&state),
+    )
+    .await?;
 
-    let res = AuthResponse {
-        access_token: token,
-    };
+    let res = issue_tokens(&state, user, refresh_token)?;
+    Ok(Json(serde_json::json!(ApiResponse::new(res))))
+}
+
+// 使用 refresh token 换取新的 access token
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    tag = "auth",
+    responses(
+        (status = 200, description = "New tokens, the old refresh token is used up", body = ApiAuth),
+        (status = 401, description = "Invalid, expired or reused refresh token", body = ErrorResponse),
+    )
+)]
+async fn refresh(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RefreshPayload>,
+) -> AppResult<Json<Value>> {
+    let refresh_token = generate_token(32);
+    let user_id = RefreshToken::rotate(
+        &state.pool,
+        &hash_token(&payload.refresh_token),
+        &hash_token(&refresh_token),
+        refresh_token_expires_at(&state),
+    )
+    .await?
+    .ok_or(Error::Auth(AuthError::InvalidToken))?;
 
+    let user = User::find_by_id(&state.pool, user_id)
+        .await?
+        .ok_or(Error::Auth(AuthError::InvalidToken))?;
+
+    let res = issue_tokens(&state, user, refresh_token)?;
     Ok(Json(serde_json::json!(ApiResponse::new(res))))
 }
 
-#[derive(Debug, Deserialize)]
+// 注销当前登录，撤销 access token 及其 refresh token
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    tag = "auth",
+    request_body(
+        content = Option<LogoutPayload>,
+        description = "Revoke the refresh token of the session too",
+    ),
+    responses(
+        (status = 200, description = "Logged out", body = ApiEmpty),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+async fn logout(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    payload: Option<Json<LogoutPayload>>,
+) -> AppResult<Json<Value>> {
+    RevokedToken::create(&state.pool, &claims.jti, claims.expires_at()).await?;
+
+    if let Some(Json(payload)) = payload {
+        RefreshToken::revoke_family(
+            &state.pool,
+            claims.user.id,
+            &hash_token(&payload.refresh_token),
+        )
+        .await?;
+    }
+
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 验证邮箱，激活账号
+#[utoipa::path(
+    get,
+    path = "/api/auth/verify",
+    tag = "auth",
+    params(VerifyQuery),
+    responses(
+        (status = 200, description = "Account activated", body = ApiEmpty),
+        (status = 401, description = "Invalid or expired link", body = ErrorResponse),
+    )
+)]
+async fn verify(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<VerifyQuery>,
+) -> AppResult<Json<Value>> {
+    let claims = jwt::decode_action(&query.token, VERIFY_ACTION, &state.secret)?;
+
+    // the link is only valid for the address it was sent to
+    let user = User::find_by_id(&state.pool, claims.sub).await?;
+    match user {
+        Some(user) if user.email == claims.email => {
+            if !user.is_active {
+                User::activate(&state.pool, user.id).await?;
+            }
+        }
+        _ => return Err(Error::Auth(AuthError::InvalidToken)),
+    }
+
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 重新发送验证邮件
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify/resend",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Sent, unless the account is unknown or active or a mail was sent recently", body = ApiEmpty),
+    )
+)]
+async fn resend_verification(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ResendPayload>,
+) -> AppResult<Json<Value>> {
+    // unknown, activated, recently mailed and failing accounts all get the same answer,
+    // so emails can not be probed
+    let user = User::find_by_name_or_email(&state.pool, &payload.email, &payload.email).await?;
+    if let Some(user) = user.filter(|u| !u.is_active && u.email == payload.email) {
+        match send_verification(&state, &user).await {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::info!("verification mail to {} sent recently, skipped", user.email)
+            }
+            Err(err) => tracing::error!("send verification mail to {}: {:?}", user.email, err),
+        }
+    }
+
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+/// Send the email verification link to the user. Returns false without sending
+/// when the previous mail was sent too recently.
+pub async fn send_verification(state: &AppState, user: &PublicUser) -> AppResult<bool> {
+    let interval = chrono::Duration::seconds(VERIFY_RESEND_INTERVAL);
+    if !User::mark_verification_sent(&state.pool, user.id, interval).await? {
+        return Ok(false);
+    }
+
+    let token = jwt::encode_action(user, VERIFY_ACTION, &state.secret, state.verify_token_ttl)?;
+    let link = format!("{}/api/auth/verify?token={}", state.site_url, token);
+    let mail = Mail {
+        to: user.email.clone(),
+        subject: String::from("Verify your email address"),
+        body: format!(
+            "Hi {},\n\nPlease open the link below to activate your account, it expires in {} hours.\n\n{}\n",
+            user.name,
+            state.verify_token_ttl.num_hours(),
+            link
+        ),
+    };
+    state.mailer.send(mail).await?;
+
+    Ok(true)
+}
+
+// 忘记密码，发送重置邮件
+#[utoipa::path(
+    post,
+    path = "/api/auth/password/forgot",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Sent, unless the email is unknown or a mail was sent recently", body = ApiEmpty),
+    )
+)]
+async fn forgot_password(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ForgotPasswordPayload>,
+) -> AppResult<Json<Value>> {
+    // unknown, recently mailed and failing addresses all get the same answer, so emails
+    // can not be probed
+    let user = User::find_by_name_or_email(&state.pool, &payload.email, &payload.email).await?;
+    if let Some(user) = user.filter(|u| u.email == payload.email) {
+        match send_password_reset(&state, &user).await {
+            Ok(true) => {}
+            Ok(false) => tracing::info!(
+                "password reset mail to {} sent recently, skipped",
+                user.email
+            ),
+            Err(err) => tracing::error!("send password reset mail to {}: {:?}", user.email, err),
+        }
+    }
+
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+/// Send a password reset link to the user. Returns false without sending when the
+/// previous one was requested too recently.
+async fn send_password_reset(state: &AppState, user: &PublicUser) -> AppResult<bool> {
+    let interval = chrono::Duration::seconds(RESET_RESEND_INTERVAL);
+    if PasswordReset::requested_recently(&state.pool, user.id, interval).await? {
+        return Ok(false);
+    }
+
+    let token = generate_token(32);
+    let expires_at = (chrono::Utc::now() + state.reset_token_ttl).naive_utc();
+    PasswordReset::create(&state.pool, user.id, &hash_token(&token), expires_at).await?;
+
+    // the frontend page passes the token on to `POST /api/auth/password/reset`
+    let link = format!(
+        "{}{}?token={}",
+        state.site_url, state.password_reset_path, token
+    );
+    let mail = Mail {
+        to: user.email.clone(),
+        subject: String::from("Reset your password"),
+        body: format!(
+            "Hi {},\n\nPlease open the link below to reset your password, it expires in {} minutes.\n\n{}\n\nIf you did not request it, just ignore this mail.\n",
+            user.name,
+            state.reset_token_ttl.num_minutes(),
+            link
+        ),
+    };
+    state.mailer.send(mail).await?;
+
+    Ok(true)
+}
+
+// 使用重置邮件中的 token 设置新密码
+#[utoipa::path(
+    post,
+    path = "/api/auth/password/reset",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Password changed, every session is logged out", body = ApiEmpty),
+        (status = 401, description = "Invalid or expired token", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    )
+)]
+async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ResetPasswordPayload>,
+) -> AppResult<Json<Value>> {
+    let user_id = PasswordReset::consume(&state.pool, &hash_token(&payload.token))
+        .await?
+        .ok_or(Error::Auth(AuthError::InvalidToken))?;
+
+    if !User::update_password(&state.pool, user_id, &payload.new_password).await? {
+        return Err(Error::NotFound(String::from("user")));
+    }
+    RefreshToken::revoke_by_user(&state.pool, user_id).await?;
+
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+fn refresh_token_expires_at(state: &AppState) -> chrono::NaiveDateTime {
+    (chrono::Utc::now() + state.refresh_token_ttl).naive_utc()
+}
+
+fn issue_tokens(
+    state: &AppState,
+    user: PublicUser,
+    refresh_token: String,
+) -> AppResult<AuthResponse> {
+    let access_token = jwt::encode(user, &state.secret, state.access_token_ttl)?;
+
+    Ok(AuthResponse {
+        access_token,
+        refresh_token,
+        token_type: String::from("Bearer"),
+        expires_in: state.access_token_ttl.num_seconds(),
+    })
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct AuthPayload {
     email: String,
     password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct RefreshPayload {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct LogoutPayload {
+    refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct VerifyQuery {
+    token: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResendPayload {
+    #[validate(email(message = "must be a valid email address"))]
+    email: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ForgotPasswordPayload {
+    #[validate(email(message = "must be a valid email address"))]
+    email: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResetPasswordPayload {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    token: String,
+    #[validate(length(min = 6, max = 64, message = "must be 6-64 characters"))]
+    new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthResponse {
     pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
 }
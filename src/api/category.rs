@@ -1,33 +1,56 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, Query, State},
-    routing::get,
-    Json, Router,
+    extract::State,
+    routing::{get, post},
+    Router,
 };
 use serde_json::Value;
 
-use super::{ApiResponse, Pagination};
+use super::{
+    extract::{Json, Path, Query},
+    ApiResponse, Pagination,
+};
 use crate::{
     errors::{AppResult, Error},
     models::category::{Category, CategoryData},
     router::AppState,
-    utils::jwt::Claims,
+    utils::{
+        jwt::Claims,
+        permission::{Admin, RequirePermission},
+    },
 };
 
 pub fn create_route() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_categories).post(create_category))
+        .route("/tree", get(get_category_tree))
+        .route("/trash", get(get_trashed_categories))
         .route(
             "/:id",
             get(get_category)
                 .put(update_category)
                 .delete(delete_category),
         )
+        .route("/:id/restore", post(restore_category))
 }
 
 // 注册新分类
+#[utoipa::path(
+    post,
+    path = "/api/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The new category", body = ApiCategory),
+        (status = 400, description = "Unknown parent category", body = ErrorResponse),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+        (status = 409, description = "The category name is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn create_category(
+    _perm: RequirePermission<Admin>,
     State(state): State<Arc<AppState>>,
     Json(category_info): Json<CategoryData>,
 ) -> AppResult<Json<Value>> {
@@ -37,6 +60,7 @@ pub async fn create_category(
             "categoryname or email has already been used",
         )));
     }
+    check_trash(&state, &category_info.name).await?;
 
     let uid = Category::create(&state.pool, &category_info).await?;
     let new_category = Category::find_by_id(&state.pool, uid as i32).await?;
@@ -50,6 +74,16 @@ pub async fn create_category(
 }
 
 // 获取分类列表
+#[utoipa::path(
+    get,
+    path = "/api/categories",
+    tag = "categories",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Categories", body = ApiCategoryPage),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn get_categories(
     _claims: Claims,
     State(state): State<Arc<AppState>>,
@@ -61,7 +95,37 @@ pub async fn get_categories(
     Ok(Json(serde_json::json!(resp)))
 }
 
+// 获取分类树
+#[utoipa::path(
+    get,
+    path = "/api/categories/tree",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Top-level categories with their subcategories nested, each level sorted by name", body = ApiCategoryTree),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_category_tree(
+    _claims: Claims,
+    State(state): State<Arc<AppState>>,
+) -> AppResult<Json<Value>> {
+    let tree = Category::find_tree(&state.pool).await?;
+
+    let resp = ApiResponse::new(tree);
+    Ok(Json(serde_json::json!(resp)))
+}
+
 // 获取指定分类
+#[utoipa::path(
+    get,
+    path = "/api/categories/{id}",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The category", body = ApiCategory),
+        (status = 404, description = "No such category", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn get_category(
     _claims: Claims,
     State(state): State<Arc<AppState>>,
@@ -78,8 +142,22 @@ pub async fn get_category(
 }
 
 // 更新指定分类的信息
+#[utoipa::path(
+    put,
+    path = "/api/categories/{id}",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The updated category, moved with its subcategories under its new parent", body = ApiCategory),
+        (status = 400, description = "Unknown parent category, or a move under itself or its subcategories", body = ErrorResponse),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+        (status = 404, description = "No such category", body = ErrorResponse),
+        (status = 409, description = "The category name is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_category(
-    _claims: Claims,
+    _perm: RequirePermission<Admin>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
     Json(category_info): Json<CategoryData>,
@@ -90,6 +168,7 @@ pub async fn update_category(
             "categoryname or email has already been used",
         )));
     }
+    check_trash(&state, &category_info.name).await?;
 
     let update_ok = Category::update(&state.pool, id, &category_info).await?;
     if !update_ok {
@@ -107,12 +186,97 @@ pub async fn update_category(
 }
 
 // 删除指定分类
+#[utoipa::path(
+    delete,
+    path = "/api/categories/{id}",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Moved to the trash", body = ApiEmpty),
+        (status = 400, description = "The category has subcategories", body = ErrorResponse),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+        (status = 404, description = "No such category", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_category(
-    _claims: Claims,
+    _perm: RequirePermission<Admin>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
 ) -> AppResult<Json<Value>> {
-    Category::delete(&state.pool, id).await?;
+    if !Category::delete(&state.pool, id).await? {
+        return Err(Error::NotFound(String::from("category")));
+    }
+
     let resp = ApiResponse::new(());
     Ok(Json(serde_json::json!(resp)))
 }
+
+// 获取回收站中的分类列表
+#[utoipa::path(
+    get,
+    path = "/api/categories/trash",
+    tag = "categories",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Deleted categories, latest deleted first", body = ApiTrashedCategoryPage),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_trashed_categories(
+    _perm: RequirePermission<Admin>,
+    State(state): State<Arc<AppState>>,
+    Query(pagination): Query<Pagination>,
+) -> AppResult<Json<Value>> {
+    let categories = Category::find_trash(&state.pool, &pagination).await?;
+
+    let resp = ApiResponse::new(categories);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 从回收站恢复指定分类
+#[utoipa::path(
+    post,
+    path = "/api/categories/{id}/restore",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The restored category", body = ApiCategory),
+        (status = 400, description = "The parent category is in the trash", body = ErrorResponse),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+        (status = 404, description = "No such category in the trash", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_category(
+    _perm: RequirePermission<Admin>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> AppResult<Json<Value>> {
+    if Category::parent_in_trash(&state.pool, id).await? {
+        return Err(Error::BadRequest(String::from(
+            "parent category is in the trash, restore it first",
+        )));
+    }
+    if !Category::restore(&state.pool, id).await? {
+        return Err(Error::NotFound(String::from("category")));
+    }
+
+    let category = Category::find_by_id(&state.pool, id).await?;
+    if category.is_none() {
+        return Err(Error::NotFound(String::from("category")));
+    }
+
+    let category = category.unwrap();
+    let resp = ApiResponse::new(category);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+/// Names of deleted categories stay taken until they are purged
+async fn check_trash(state: &AppState, name: &str) -> AppResult<()> {
+    if Category::exists_in_trash(&state.pool, name).await? {
+        return Err(Error::ObjectConflict(String::from(
+            "category name is held by a deleted category",
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,548 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    routing::{get, post},
+    Router,
+};
+use serde_json::Value;
+
+use super::{
+    article::{find_unlocked, LikeState},
+    extract::{Json, Path, Query},
+    ApiResponse, Pagination,
+};
+use crate::{
+    errors::{AppResult, Error},
+    models::{
+        comment::{Comment, CommentData, PublicComment},
+        like::{Like, LikeTarget},
+        reply::{CreateReply, PublicReply, Reply, UpdateReply},
+        role::Permission,
+    },
+    router::AppState,
+    utils::{
+        jwt::Claims,
+        permission::{Comment as CommentPermission, RequirePermission},
+    },
+};
+
+/// Routes nested under `/articles/:id/comments`
+pub fn create_route() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_comments).post(create_comment))
+        .route(
+            "/:comment_id",
+            get(get_comment).put(update_comment).delete(delete_comment),
+        )
+        .route(
+            "/:comment_id/like",
+            post(like_comment).delete(unlike_comment),
+        )
+        .route("/:comment_id/replies", get(get_replies).post(create_reply))
+        .route(
+            "/:comment_id/replies/:reply_id",
+            get(get_reply).put(update_reply).delete(delete_reply),
+        )
+        .route(
+            "/:comment_id/replies/:reply_id/like",
+            post(like_reply).delete(unlike_reply),
+        )
+}
+
+// 发表评论
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/comments",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The new comment", body = ApiComment),
+        (status = 403, description = "Not allowed to comment, or the article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_comment(
+    claims: RequirePermission<CommentPermission>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(article_id): Path<i32>,
+    Json(comment_info): Json<CommentData>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims.claims), &headers, article_id).await?;
+    let id = Comment::create(&state.pool, article_id, claims.user.id, &comment_info).await?;
+    let new_comment = Comment::find_by_id(&state.pool, id as i32, Some(claims.user.id)).await?;
+    if new_comment.is_none() {
+        return Err(Error::NotFound(String::from("comment")));
+    }
+
+    let new_comment = new_comment.unwrap();
+    let resp = ApiResponse::new(new_comment);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 获取文章的评论列表
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/comments",
+    tag = "comments",
+    params(
+        Pagination,
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "Comments, newest first, each with the first replies of its thread", body = ApiCommentPage),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn get_comments(
+    claims: Option<Claims>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(article_id): Path<i32>,
+    Query(pagination): Query<Pagination>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, claims.as_ref(), &headers, article_id).await?;
+    let viewer = claims.map(|c| c.user.id);
+    let comments = Comment::find_list(&state.pool, article_id, viewer, &pagination).await?;
+
+    let resp = ApiResponse::new(comments);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 获取指定评论
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/comments/{comment_id}",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The comment", body = ApiComment),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article or comment", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn get_comment(
+    claims: Option<Claims>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, id)): Path<(i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, claims.as_ref(), &headers, article_id).await?;
+    let viewer = claims.map(|c| c.user.id);
+    let comment = find_comment(&state, article_id, id, viewer).await?;
+
+    let resp = ApiResponse::new(comment);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 编辑指定评论
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}/comments/{comment_id}",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The updated comment", body = ApiComment),
+        (status = 403, description = "Neither the author nor a moderator, or the article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article or comment", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_comment(
+    claims: RequirePermission<CommentPermission>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, id)): Path<(i32, i32)>,
+    Json(comment_info): Json<CommentData>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims.claims), &headers, article_id).await?;
+    let comment = find_comment(&state, article_id, id, Some(claims.user.id)).await?;
+    check_author(&claims, comment.user_id)?;
+
+    let update_ok = Comment::update(&state.pool, id, &comment_info).await?;
+    if !update_ok {
+        return Err(Error::NotFound(String::from("comment")));
+    }
+
+    let comment = find_comment(&state, article_id, id, Some(claims.user.id)).await?;
+    let resp = ApiResponse::new(comment);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 删除指定评论及其回复
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/comments/{comment_id}",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "Deleted along with its replies", body = ApiEmpty),
+        (status = 403, description = "Neither the author nor a moderator, or the article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article or comment", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_comment(
+    claims: RequirePermission<CommentPermission>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, id)): Path<(i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims.claims), &headers, article_id).await?;
+    let comment = find_comment(&state, article_id, id, Some(claims.user.id)).await?;
+    check_author(&claims, comment.user_id)?;
+
+    Comment::delete(&state.pool, id).await?;
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 回复评论或回复
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/comments/{comment_id}/replies",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The new reply", body = ApiReply),
+        (status = 403, description = "Not allowed to comment, or the article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article, comment or reply to answer", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_reply(
+    claims: RequirePermission<CommentPermission>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, comment_id)): Path<(i32, i32)>,
+    Json(reply_info): Json<CreateReply>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims.claims), &headers, article_id).await?;
+    let viewer = Some(claims.user.id);
+    find_comment(&state, article_id, comment_id, viewer).await?;
+    if let Some(reply_id) = reply_info.reply_id {
+        find_reply(&state, comment_id, reply_id, viewer).await?;
+    }
+
+    let id = Reply::create(&state.pool, comment_id, claims.user.id, &reply_info).await?;
+    let new_reply = Reply::find_by_id(&state.pool, id as i32, viewer).await?;
+    if new_reply.is_none() {
+        return Err(Error::NotFound(String::from("reply")));
+    }
+
+    let new_reply = new_reply.unwrap();
+    let resp = ApiResponse::new(new_reply);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 获取评论下的回复列表
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/comments/{comment_id}/replies",
+    tag = "comments",
+    params(
+        Pagination,
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "Replies of the thread, oldest first", body = ApiReplyPage),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article or comment", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn get_replies(
+    claims: Option<Claims>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, comment_id)): Path<(i32, i32)>,
+    Query(pagination): Query<Pagination>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, claims.as_ref(), &headers, article_id).await?;
+    let viewer = claims.map(|c| c.user.id);
+    find_comment(&state, article_id, comment_id, viewer).await?;
+    let replies = Reply::find_list(&state.pool, comment_id, viewer, &pagination).await?;
+
+    let resp = ApiResponse::new(replies);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 获取指定回复
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/comments/{comment_id}/replies/{reply_id}",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The reply", body = ApiReply),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article, comment or reply", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn get_reply(
+    claims: Option<Claims>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, comment_id, id)): Path<(i32, i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, claims.as_ref(), &headers, article_id).await?;
+    let viewer = claims.map(|c| c.user.id);
+    find_comment(&state, article_id, comment_id, viewer).await?;
+    let reply = find_reply(&state, comment_id, id, viewer).await?;
+
+    let resp = ApiResponse::new(reply);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 编辑指定回复
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}/comments/{comment_id}/replies/{reply_id}",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "The updated reply", body = ApiReply),
+        (status = 403, description = "Neither the author nor a moderator, or the article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article, comment or reply", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_reply(
+    claims: RequirePermission<CommentPermission>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, comment_id, id)): Path<(i32, i32, i32)>,
+    Json(reply_info): Json<UpdateReply>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims.claims), &headers, article_id).await?;
+    let viewer = Some(claims.user.id);
+    find_comment(&state, article_id, comment_id, viewer).await?;
+    let reply = find_reply(&state, comment_id, id, viewer).await?;
+    check_author(&claims, reply.user_id)?;
+
+    let update_ok = Reply::update(&state.pool, id, &reply_info).await?;
+    if !update_ok {
+        return Err(Error::NotFound(String::from("reply")));
+    }
+
+    let reply = find_reply(&state, comment_id, id, viewer).await?;
+    let resp = ApiResponse::new(reply);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 删除指定回复及其下级回复
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/comments/{comment_id}/replies/{reply_id}",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "Deleted along with the replies answering it", body = ApiEmpty),
+        (status = 403, description = "Neither the author nor a moderator, or the article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article, comment or reply", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_reply(
+    claims: RequirePermission<CommentPermission>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, comment_id, id)): Path<(i32, i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims.claims), &headers, article_id).await?;
+    let viewer = Some(claims.user.id);
+    find_comment(&state, article_id, comment_id, viewer).await?;
+    let reply = find_reply(&state, comment_id, id, viewer).await?;
+    check_author(&claims, reply.user_id)?;
+
+    Reply::delete(&state.pool, id).await?;
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 点赞指定评论
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/comments/{comment_id}/like",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "Liked, liking twice counts once", body = ApiLikeState),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article or comment", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn like_comment(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, id)): Path<(i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims), &headers, article_id).await?;
+    find_comment(&state, article_id, id, None).await?;
+    set_like(&state, &claims, LikeTarget::Comment, id, true).await
+}
+
+// 取消点赞指定评论
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/comments/{comment_id}/like",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "No longer liked", body = ApiLikeState),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article or comment", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn unlike_comment(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, id)): Path<(i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims), &headers, article_id).await?;
+    find_comment(&state, article_id, id, None).await?;
+    set_like(&state, &claims, LikeTarget::Comment, id, false).await
+}
+
+// 点赞指定回复
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/comments/{comment_id}/replies/{reply_id}/like",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "Liked, liking twice counts once", body = ApiLikeState),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article, comment or reply", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn like_reply(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, comment_id, id)): Path<(i32, i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims), &headers, article_id).await?;
+    find_comment(&state, article_id, comment_id, None).await?;
+    find_reply(&state, comment_id, id, None).await?;
+    set_like(&state, &claims, LikeTarget::Reply, id, true).await
+}
+
+// 取消点赞指定回复
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/comments/{comment_id}/replies/{reply_id}/like",
+    tag = "comments",
+    params(
+        ("x-article-token" = Option<String>, Header, description = "Token of `POST /api/articles/{id}/unlock`, for password protected articles"),
+    ),
+    responses(
+        (status = 200, description = "No longer liked", body = ApiLikeState),
+        (status = 403, description = "The article is locked", body = ErrorResponse),
+        (status = 404, description = "No such article, comment or reply", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn unlike_reply(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((article_id, comment_id, id)): Path<(i32, i32, i32)>,
+) -> AppResult<Json<Value>> {
+    find_unlocked(&state, Some(&claims), &headers, article_id).await?;
+    find_comment(&state, article_id, comment_id, None).await?;
+    find_reply(&state, comment_id, id, None).await?;
+    set_like(&state, &claims, LikeTarget::Reply, id, false).await
+}
+
+async fn set_like(
+    state: &AppState,
+    claims: &Claims,
+    target: LikeTarget,
+    id: i32,
+    liked: bool,
+) -> AppResult<Json<Value>> {
+    let like_count = if liked {
+        Like::add(&state.pool, target, id, claims.user.id).await?
+    } else {
+        Like::remove(&state.pool, target, id, claims.user.id).await?
+    };
+    let like_count = match (like_count, target) {
+        (Some(like_count), _) => like_count,
+        (None, LikeTarget::Reply) => return Err(Error::NotFound(String::from("reply"))),
+        (None, _) => return Err(Error::NotFound(String::from("comment"))),
+    };
+
+    let resp = ApiResponse::new(LikeState { liked, like_count });
+    Ok(Json(serde_json::json!(resp)))
+}
+
+async fn find_comment(
+    state: &AppState,
+    article_id: i32,
+    id: i32,
+    viewer: Option<i32>,
+) -> AppResult<PublicComment> {
+    match Comment::find_by_id(&state.pool, id, viewer).await? {
+        Some(comment) if comment.article_id == article_id => Ok(comment),
+        _ => Err(Error::NotFound(String::from("comment"))),
+    }
+}
+
+async fn find_reply(
+    state: &AppState,
+    comment_id: i32,
+    id: i32,
+    viewer: Option<i32>,
+) -> AppResult<PublicReply> {
+    match Reply::find_by_id(&state.pool, id, viewer).await? {
+        Some(reply) if reply.comment_id == Some(comment_id) => Ok(reply),
+        _ => Err(Error::NotFound(String::from("reply"))),
+    }
+}
+
+/// Only the author or a moderator may change a comment or reply
+fn check_author(claims: &RequirePermission<CommentPermission>, user_id: i32) -> AppResult<()> {
+    if user_id != claims.user.id && !claims.has_permission(Permission::Moderate) {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
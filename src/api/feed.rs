@@ -0,0 +1,270 @@
+use std::{convert::Infallible, sync::Arc, time::SystemTime};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, State},
+    headers::{ETag, IfModifiedSince, IfNoneMatch, LastModified},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router, TypedHeader,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::{article::lock_protected, extract::Query, Pagination};
+use crate::{
+    errors::{AppResult, Error},
+    feed::{self, Feed, FeedFormat},
+    models::{
+        article::{Article, ArticleFilter, ArticleSort, Visibility},
+        category::Category,
+        tag::Tag,
+        user::User,
+    },
+    router::AppState,
+    utils::hash::hash_token,
+};
+
+pub fn create_route() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/feed.xml", get(get_rss_feed))
+        .route("/atom.xml", get(get_atom_feed))
+        .route("/feed.json", get(get_json_feed))
+}
+
+/// Narrow a feed down to a category, a tag (by id or name) or an author
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct FeedQuery {
+    pub category_id: Option<i32>,
+    pub tag_id: Option<i32>,
+    pub tag: Option<String>,
+    pub user_id: Option<i32>,
+}
+
+/// Conditional request headers, `If-None-Match` wins when both are sent
+pub struct Conditions {
+    if_none_match: Option<IfNoneMatch>,
+    if_modified_since: Option<IfModifiedSince>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Conditions
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let if_none_match = TypedHeader::<IfNoneMatch>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|TypedHeader(h)| h);
+        let if_modified_since = TypedHeader::<IfModifiedSince>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|TypedHeader(h)| h);
+
+        Ok(Conditions {
+            if_none_match,
+            if_modified_since,
+        })
+    }
+}
+
+// 获取 RSS 2.0 订阅
+#[utoipa::path(
+    get,
+    path = "/feed.xml",
+    tag = "feeds",
+    params(
+        FeedQuery,
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy of the feed"),
+        ("If-Modified-Since" = Option<String>, Header, description = "`Last-Modified` of a copy of the feed"),
+    ),
+    responses(
+        (status = 200, description = "The latest published articles as RSS 2.0", body = String, content_type = "application/rss+xml"),
+        (status = 304, description = "The copy of the client is current"),
+        (status = 404, description = "No such category, tag or author", body = ErrorResponse),
+    )
+)]
+pub async fn get_rss_feed(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
+    conditions: Conditions,
+    Query(query): Query<FeedQuery>,
+) -> AppResult<Response> {
+    serve_feed(
+        &state,
+        &uri.to_string(),
+        &query,
+        conditions,
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+// 获取 Atom 订阅
+#[utoipa::path(
+    get,
+    path = "/atom.xml",
+    tag = "feeds",
+    params(
+        FeedQuery,
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy of the feed"),
+        ("If-Modified-Since" = Option<String>, Header, description = "`Last-Modified` of a copy of the feed"),
+    ),
+    responses(
+        (status = 200, description = "The latest published articles as Atom", body = String, content_type = "application/atom+xml"),
+        (status = 304, description = "The copy of the client is current"),
+        (status = 404, description = "No such category, tag or author", body = ErrorResponse),
+    )
+)]
+pub async fn get_atom_feed(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
+    conditions: Conditions,
+    Query(query): Query<FeedQuery>,
+) -> AppResult<Response> {
+    serve_feed(
+        &state,
+        &uri.to_string(),
+        &query,
+        conditions,
+        FeedFormat::Atom,
+    )
+    .await
+}
+
+// 获取 JSON Feed 订阅
+#[utoipa::path(
+    get,
+    path = "/feed.json",
+    tag = "feeds",
+    params(
+        FeedQuery,
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a copy of the feed"),
+        ("If-Modified-Since" = Option<String>, Header, description = "`Last-Modified` of a copy of the feed"),
+    ),
+    responses(
+        (status = 200, description = "The latest published articles as JSON Feed", body = String, content_type = "application/feed+json"),
+        (status = 304, description = "The copy of the client is current"),
+        (status = 404, description = "No such category, tag or author", body = ErrorResponse),
+    )
+)]
+pub async fn get_json_feed(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
+    conditions: Conditions,
+    Query(query): Query<FeedQuery>,
+) -> AppResult<Response> {
+    serve_feed(
+        &state,
+        &uri.to_string(),
+        &query,
+        conditions,
+        FeedFormat::Json,
+    )
+    .await
+}
+
+/// Render the feed, or answer `304 Not Modified` when the client's copy is current.
+/// The `ETag` is a hash of the document, so articles leaving the feed change it too.
+async fn serve_feed(
+    state: &AppState,
+    path: &str,
+    query: &FeedQuery,
+    conditions: Conditions,
+    format: FeedFormat,
+) -> AppResult<Response> {
+    let feed = build_feed(state, path, query).await?;
+    let body = feed.render(format);
+
+    let etag: ETag = format!("\"{}\"", hash_token(&body))
+        .parse()
+        .expect("a hex digest is a valid etag");
+    let last_modified = feed
+        .last_modified()
+        .map(|at| SystemTime::from(feed::utc(at)));
+    let not_modified = match (conditions.if_none_match, conditions.if_modified_since) {
+        (Some(if_none_match), _) => !if_none_match.precondition_passes(&etag),
+        (None, Some(if_modified_since)) => {
+            last_modified.is_some_and(|at| !if_modified_since.is_modified(at))
+        }
+        (None, None) => false,
+    };
+
+    let headers = (
+        TypedHeader(etag),
+        last_modified.map(|at| TypedHeader(LastModified::from(at))),
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+    );
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+
+    Ok((
+        headers,
+        [(header::CONTENT_TYPE, format.content_type())],
+        body,
+    )
+        .into_response())
+}
+
+/// The latest published articles matching `query`, titled after what they were narrowed to
+async fn build_feed<'a>(state: &'a AppState, path: &str, query: &FeedQuery) -> AppResult<Feed<'a>> {
+    let mut title = state.site_title.clone();
+    let mut author = state.site_title.clone();
+    if let Some(category_id) = query.category_id {
+        let category = Category::find_by_id(&state.pool, category_id).await?;
+        if category.is_none() {
+            return Err(Error::NotFound(String::from("category")));
+        }
+        title = format!("{} - {}", title, category.unwrap().name);
+    }
+    if let Some(tag_id) = query.tag_id {
+        let tag = Tag::find_by_id(&state.pool, tag_id).await?;
+        if tag.is_none() {
+            return Err(Error::NotFound(String::from("tag")));
+        }
+        title = format!("{} - #{}", title, tag.unwrap().name);
+    }
+    if let Some(tag) = &query.tag {
+        title = format!("{} - #{}", title, tag);
+    }
+    if let Some(user_id) = query.user_id {
+        let user = User::find_by_id(&state.pool, user_id).await?;
+        if user.is_none() {
+            return Err(Error::NotFound(String::from("user")));
+        }
+        author = user.unwrap().name;
+        title = format!("{} - {}", title, author);
+    }
+
+    // only published articles, newest first, pinned ones are no exception
+    let filter = ArticleFilter {
+        category_id: query.category_id,
+        tag_id: query.tag_id,
+        tag: query.tag.clone(),
+        user_id: query.user_id,
+        top_first: Some(false),
+        sort: Some(ArticleSort::PublishedAt),
+        visibility: Visibility::Public,
+        ..Default::default()
+    };
+    let pagination = Pagination {
+        page: Some(1),
+        page_size: Some(state.feed_size),
+    };
+    let mut articles = Article::find_list(&state.pool, &filter, None, &pagination).await?;
+    lock_protected(Visibility::Public, &mut articles.list);
+
+    Ok(Feed {
+        title,
+        description: state.site_description.clone(),
+        site_url: &state.site_url,
+        feed_url: format!("{}{}", state.server_url, path),
+        author,
+        articles: articles.list,
+    })
+}
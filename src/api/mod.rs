@@ -2,13 +2,35 @@ use std::sync::Arc;
 
 use axum::Router;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::router::AppState;
+use crate::{
+    models::{
+        article::{ArticleDetail, TrashedArticle},
+        category::{CategoryNode, PublicCategory, TrashedCategory},
+        comment::{CommentThread, PublicComment},
+        reply::PublicReply,
+        revision::{PublicRevision, RevisionItem},
+        tag::{PublicTag, TrashedTag},
+        user::PublicUser,
+    },
+    router::AppState,
+    search::SearchHit,
+};
 
 pub mod article;
 pub mod auth;
 pub mod category;
+pub mod comment;
+pub mod extract;
+pub mod feed;
+pub mod openapi;
+pub mod revision;
+pub mod search;
+pub mod sitemap;
 pub mod tag;
+pub mod theme;
 pub mod user;
 
 pub fn create_route() -> Router<Arc<AppState>> {
@@ -17,10 +39,41 @@ pub fn create_route() -> Router<Arc<AppState>> {
         .nest("/categories", category::create_route())
         .nest("/tags", tag::create_route())
         .nest("/articles", article::create_route())
+        .nest("/search", search::create_route())
         .nest("/auth", auth::create_route())
+        .nest("/themes", theme::create_route())
 }
 
-#[derive(Serialize, Debug)]
+/// The envelope of every successful response, `code` is always 0
+#[derive(Serialize, Debug, ToSchema)]
+#[aliases(
+    ApiEmpty = ApiResponse<Value>,
+    ApiAuth = ApiResponse<auth::AuthResponse>,
+    ApiUser = ApiResponse<PublicUser>,
+    ApiUserPage = ApiResponse<UserPage>,
+    ApiCategory = ApiResponse<PublicCategory>,
+    ApiCategoryPage = ApiResponse<CategoryPage>,
+    ApiCategoryTree = ApiResponse<Vec<CategoryNode>>,
+    ApiTrashedCategoryPage = ApiResponse<TrashedCategoryPage>,
+    ApiTag = ApiResponse<PublicTag>,
+    ApiTagPage = ApiResponse<TagPage>,
+    ApiTrashedTagPage = ApiResponse<TrashedTagPage>,
+    ApiArticle = ApiResponse<ArticleDetail>,
+    ApiArticlePage = ApiResponse<ArticlePage>,
+    ApiTrashedArticlePage = ApiResponse<TrashedArticlePage>,
+    ApiPreview = ApiResponse<article::Preview>,
+    ApiLikeState = ApiResponse<article::LikeState>,
+    ApiUnlockToken = ApiResponse<article::UnlockToken>,
+    ApiArticleViews = ApiResponse<article::ArticleViews>,
+    ApiComment = ApiResponse<PublicComment>,
+    ApiCommentPage = ApiResponse<CommentPage>,
+    ApiReply = ApiResponse<PublicReply>,
+    ApiReplyPage = ApiResponse<ReplyPage>,
+    ApiRevision = ApiResponse<PublicRevision>,
+    ApiRevisionPage = ApiResponse<RevisionPage>,
+    ApiRevisionDiff = ApiResponse<revision::RevisionDiff>,
+    ApiSearchPage = ApiResponse<SearchPage>
+)]
 pub struct ApiResponse<T> {
     pub code: u32,
     pub message: String,
@@ -47,13 +100,46 @@ impl<T> ApiResponse<T> {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
 pub struct Pagination {
+    /// Starting from 1
+    #[param(minimum = 1, default = 1)]
     pub page: Option<i32>,
+    #[param(minimum = 1, default = 10)]
     pub page_size: Option<i32>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(
+    UserPage = PaginationResponse<PublicUser>,
+    CategoryPage = PaginationResponse<PublicCategory>,
+    TrashedCategoryPage = PaginationResponse<TrashedCategory>,
+    TagPage = PaginationResponse<PublicTag>,
+    TrashedTagPage = PaginationResponse<TrashedTag>,
+    ArticlePage = PaginationResponse<ArticleDetail>,
+    TrashedArticlePage = PaginationResponse<TrashedArticle>,
+    CommentPage = PaginationResponse<CommentThread>,
+    ReplyPage = PaginationResponse<PublicReply>,
+    RevisionPage = PaginationResponse<RevisionItem>,
+    SearchPage = PaginationResponse<SearchHit>
+)]
 pub struct PaginationResponse<T> {
     pub page: i32,
     pub page_size: i32,
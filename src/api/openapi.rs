@@ -0,0 +1,236 @@
+//! OpenAPI 3 document of the REST API, generated from the handlers and the models they
+//! take and return. It is served at `/api/openapi.json` with Swagger UI at `/api/docs`,
+//! and written to disk by `vars openapi` for client generators.
+
+use std::sync::Arc;
+
+use axum::Router;
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        OpenApi as Document,
+    },
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::{
+    article, auth, category, comment, feed, revision, search, sitemap, tag, theme, user, ApiEmpty,
+    SortOrder, UserPage,
+};
+use crate::{
+    errors::{Error, ErrorResponse},
+    models::{
+        article::{
+            ArticleDetail, ArticleSort, ArticleStatus, ArticleStatusData, CreateArticle,
+            PreviewArticle, PublicArticle, TrashedArticle, UnlockArticle, UpdateArticle,
+        },
+        article_view::DailyViews,
+        category::{CategoryData, CategoryNode, CategorySummary, PublicCategory, TrashedCategory},
+        comment::{CommentData, CommentThread, PublicComment},
+        reply::{CreateReply, PublicReply, UpdateReply},
+        revision::{PublicRevision, RevisionItem},
+        tag::{PublicTag, TagData, TagSummary, TrashedTag},
+        user::{CreateUser, PublicUser, UpdatePassword, UpdateUser},
+    },
+    router::AppState,
+    search::SearchHit,
+    utils::{
+        diff::DiffLine,
+        markdown::{Outline, TocItem},
+    },
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        auth::authorize,
+        auth::refresh,
+        auth::logout,
+        auth::verify,
+        auth::resend_verification,
+        auth::forgot_password,
+        auth::reset_password,
+        user::get_users,
+        user::create_user,
+        user::get_user_profile,
+        user::edit_user_profile,
+        user::change_password,
+        user::get_trashed_users,
+        user::get_user,
+        user::update_user,
+        user::delete_user,
+        user::restore_user,
+        category::get_categories,
+        category::create_category,
+        category::get_category_tree,
+        category::get_trashed_categories,
+        category::get_category,
+        category::update_category,
+        category::delete_category,
+        category::restore_category,
+        tag::get_tags,
+        tag::create_tag,
+        tag::get_trashed_tags,
+        tag::get_tag,
+        tag::update_tag,
+        tag::delete_tag,
+        tag::get_tag_articles,
+        tag::restore_tag,
+        article::get_articles,
+        article::create_article,
+        article::preview_article,
+        article::get_article_by_slug,
+        article::get_trashed_articles,
+        article::get_article,
+        article::update_article,
+        article::delete_article,
+        article::update_article_status,
+        article::restore_article,
+        article::like_article,
+        article::unlike_article,
+        article::unlock_article,
+        article::get_article_views,
+        comment::get_comments,
+        comment::create_comment,
+        comment::get_comment,
+        comment::update_comment,
+        comment::delete_comment,
+        comment::like_comment,
+        comment::unlike_comment,
+        comment::get_replies,
+        comment::create_reply,
+        comment::get_reply,
+        comment::update_reply,
+        comment::delete_reply,
+        comment::like_reply,
+        comment::unlike_reply,
+        revision::get_revisions,
+        revision::diff_revisions,
+        revision::get_revision,
+        revision::restore_revision,
+        search::search_articles,
+        theme::get_highlight_css,
+        feed::get_rss_feed,
+        feed::get_atom_feed,
+        feed::get_json_feed,
+        sitemap::get_robots,
+        sitemap::get_sitemap,
+        sitemap::get_pages_sitemap,
+        sitemap::get_articles_sitemap,
+    ),
+    components(schemas(
+        // the generic envelopes, any of their aliases brings in all of them
+        ApiEmpty,
+        UserPage,
+        ErrorResponse,
+        auth::AuthPayload,
+        auth::RefreshPayload,
+        auth::LogoutPayload,
+        auth::ResendPayload,
+        auth::ForgotPasswordPayload,
+        auth::ResetPasswordPayload,
+        auth::AuthResponse,
+        CreateUser,
+        UpdateUser,
+        UpdatePassword,
+        PublicUser,
+        CategoryData,
+        PublicCategory,
+        TrashedCategory,
+        CategoryNode,
+        CategorySummary,
+        TagData,
+        PublicTag,
+        TagSummary,
+        TrashedTag,
+        ArticleStatus,
+        ArticleSort,
+        SortOrder,
+        CreateArticle,
+        UpdateArticle,
+        ArticleStatusData,
+        PreviewArticle,
+        UnlockArticle,
+        PublicArticle,
+        ArticleDetail,
+        TrashedArticle,
+        Outline,
+        TocItem,
+        DailyViews,
+        article::Preview,
+        article::LikeState,
+        article::UnlockToken,
+        article::ArticleViews,
+        CommentData,
+        PublicComment,
+        CommentThread,
+        CreateReply,
+        UpdateReply,
+        PublicReply,
+        PublicRevision,
+        RevisionItem,
+        DiffLine,
+        revision::RevisionDiff,
+        SearchHit,
+    )),
+    modifiers(&BearerAuth, &ErrorCodes),
+    tags(
+        (name = "auth", description = "Login, sessions, email verification and password reset"),
+        (name = "users", description = "Accounts and the current user's profile"),
+        (name = "categories", description = "Article categories and their hierarchy"),
+        (name = "tags", description = "Article tags"),
+        (name = "articles", description = "Articles, their publication, likes and views"),
+        (name = "comments", description = "Comments on articles and the replies in their threads"),
+        (name = "revisions", description = "Edit history of articles"),
+        (name = "search", description = "Full-text search of published articles"),
+        (name = "themes", description = "Styles of rendered article content"),
+        (name = "feeds", description = "RSS, Atom and JSON feeds of published articles"),
+        (name = "sitemap", description = "Sitemaps and robots.txt for search engines"),
+    )
+)]
+pub struct ApiDoc;
+
+pub fn create_route() -> Router<Arc<AppState>> {
+    Router::new().merge(SwaggerUi::new("/api/docs").url("/api/openapi.json", ApiDoc::openapi()))
+}
+
+/// Access tokens of `POST /api/auth` go in the `Authorization: Bearer` header
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut Document) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// List the error codes in the description of the API, they are what clients branch on
+struct ErrorCodes;
+
+impl Modify for ErrorCodes {
+    fn modify(&self, openapi: &mut Document) {
+        let mut description = String::from(
+            "Successful responses wrap their data in `{ code: 0, message, data }`, errors answer \
+             with an `ErrorResponse` whose `code` tells what went wrong:\n\n\
+             | code | status | meaning |\n| --- | --- | --- |\n",
+        );
+        for (error, meaning) in Error::catalog() {
+            description.push_str(&format!(
+                "| {} | {} | {} |\n",
+                error.code(),
+                error.status().as_u16(),
+                meaning
+            ));
+        }
+        openapi.info.description = Some(description);
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use super::{
+    article,
+    extract::{Json, Path, Query},
+    ApiResponse, Pagination,
+};
+use crate::{
+    errors::{AppResult, Error},
+    models::{
+        article::Article,
+        revision::{ArticleRevision, PublicRevision},
+        role::Permission,
+    },
+    router::AppState,
+    utils::{
+        diff::{self, DiffLine},
+        permission::{RequirePermission, Write},
+    },
+};
+
+/// Routes nested under `/articles/:id/revisions`
+pub fn create_route() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_revisions))
+        .route("/diff", get(diff_revisions))
+        .route("/:rev", get(get_revision))
+        .route("/:rev/restore", post(restore_revision))
+}
+
+// 获取文章的修订历史
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/revisions",
+    tag = "revisions",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Revisions without their content, newest first", body = ApiRevisionPage),
+        (status = 403, description = "Neither the author nor a moderator", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_revisions(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Path(article_id): Path<i32>,
+    Query(pagination): Query<Pagination>,
+) -> AppResult<Json<Value>> {
+    check_editor(&state, &claims, article_id).await?;
+    let revisions = ArticleRevision::find_list(&state.pool, article_id, &pagination).await?;
+
+    let resp = ApiResponse::new(revisions);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 获取指定修订版本
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/revisions/{rev}",
+    tag = "revisions",
+    responses(
+        (status = 200, description = "The revision", body = ApiRevision),
+        (status = 403, description = "Neither the author nor a moderator", body = ErrorResponse),
+        (status = 404, description = "No such article or revision", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_revision(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Path((article_id, id)): Path<(i32, i32)>,
+) -> AppResult<Json<Value>> {
+    check_editor(&state, &claims, article_id).await?;
+    let revision = find_revision(&state, article_id, id).await?;
+
+    let resp = ApiResponse::new(revision);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 比较两个修订版本
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/revisions/diff",
+    tag = "revisions",
+    params(DiffQuery),
+    responses(
+        (status = 200, description = "Line diff of the title, summary and content", body = ApiRevisionDiff),
+        (status = 403, description = "Neither the author nor a moderator", body = ErrorResponse),
+        (status = 404, description = "No such article or revision", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn diff_revisions(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Path(article_id): Path<i32>,
+    Query(query): Query<DiffQuery>,
+) -> AppResult<Json<Value>> {
+    check_editor(&state, &claims, article_id).await?;
+    let from = find_revision(&state, article_id, query.from).await?;
+    let to = match query.to {
+        Some(to) => find_revision(&state, article_id, to).await?,
+        None => ArticleRevision::find_latest(&state.pool, article_id)
+            .await?
+            .ok_or(Error::NotFound(String::from("revision")))?,
+    };
+
+    let resp = ApiResponse::new(RevisionDiff {
+        from: from.id,
+        to: to.id,
+        title: diff::line_diff(&from.title, &to.title),
+        summary: diff::line_diff(
+            from.summary.as_deref().unwrap_or_default(),
+            to.summary.as_deref().unwrap_or_default(),
+        ),
+        content: diff::line_diff(&from.content, &to.content),
+    });
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 恢复到指定修订版本
+#[utoipa::path(
+    post,
+    path = "/api/articles/{id}/revisions/{rev}/restore",
+    tag = "revisions",
+    responses(
+        (status = 200, description = "The article, saved again as a new revision", body = ApiArticle),
+        (status = 403, description = "Neither the author nor a moderator", body = ErrorResponse),
+        (status = 404, description = "No such article or revision", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_revision(
+    claims: RequirePermission<Write>,
+    State(state): State<Arc<AppState>>,
+    Path((article_id, id)): Path<(i32, i32)>,
+) -> AppResult<Json<Value>> {
+    check_editor(&state, &claims, article_id).await?;
+    let revision = find_revision(&state, article_id, id).await?;
+
+    let restore_ok = Article::restore_revision(
+        &state.pool,
+        article_id,
+        claims.user.id,
+        &revision,
+        state.revision_retention,
+    )
+    .await?;
+    if !restore_ok {
+        return Err(Error::NotFound(String::from("article")));
+    }
+    article::reindex(&state, &[article_id]).await;
+
+    let article = Article::find_detail(&state.pool, article_id, Some(claims.user.id)).await?;
+    if article.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+
+    let article = article.unwrap();
+    let resp = ApiResponse::new(article);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+/// The history of an article is only open to its author and moderators
+async fn check_editor(
+    state: &AppState,
+    claims: &RequirePermission<Write>,
+    article_id: i32,
+) -> AppResult<()> {
+    let article = Article::find_by_id(&state.pool, article_id).await?;
+    if article.is_none() {
+        return Err(Error::NotFound(String::from("article")));
+    }
+    if article.unwrap().user_id != claims.user.id && !claims.has_permission(Permission::Moderate) {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+async fn find_revision(state: &AppState, article_id: i32, id: i32) -> AppResult<PublicRevision> {
+    ArticleRevision::find_by_id(&state.pool, article_id, id)
+        .await?
+        .ok_or(Error::NotFound(String::from("revision")))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DiffQuery {
+    from: i32,
+    // the newest revision, i.e. the current article, when absent
+    to: Option<i32>,
+}
+
+/// Changes from revision `from` to revision `to`, each field as hunks of lines
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    pub title: Vec<Vec<DiffLine>>,
+    pub summary: Vec<Vec<DiffLine>>,
+    pub content: Vec<Vec<DiffLine>>,
+}
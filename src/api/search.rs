@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::{extract::State, routing::get, Router};
+use serde_json::Value;
+
+use super::{
+    extract::{Json, Query},
+    ApiResponse, Pagination,
+};
+use crate::{errors::AppResult, router::AppState, search::SearchQuery};
+
+pub fn create_route() -> Router<Arc<AppState>> {
+    Router::new().route("/", get(search_articles))
+}
+
+// 全文搜索已发布的文章，按相关度排序并高亮匹配内容
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    tag = "search",
+    params(Pagination, SearchQuery),
+    responses(
+        (status = 200, description = "Published articles, best matches first", body = ApiSearchPage),
+    )
+)]
+pub async fn search_articles(
+    State(state): State<Arc<AppState>>,
+    Query(pagination): Query<Pagination>,
+    Query(query): Query<SearchQuery>,
+) -> AppResult<Json<Value>> {
+    let results = state.search.search(query, pagination).await?;
+
+    let resp = ApiResponse::new(results);
+    Ok(Json(serde_json::json!(resp)))
+}
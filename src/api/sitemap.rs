@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use super::extract::Path;
+use crate::{
+    errors::{AppResult, Error},
+    router::AppState,
+    sitemap,
+};
+
+pub fn create_route() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/robots.txt", get(get_robots))
+        .route("/sitemap.xml", get(get_sitemap))
+        .route("/sitemaps/pages.xml", get(get_pages_sitemap))
+        .route("/sitemaps/articles/:file", get(get_articles_sitemap))
+}
+
+// 获取 robots.txt
+#[utoipa::path(
+    get,
+    path = "/robots.txt",
+    tag = "sitemap",
+    responses(
+        (status = 200, description = "Crawler rules pointing to the sitemap", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn get_robots(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        state.robots_txt.clone(),
+    )
+}
+
+// 获取站点地图，文章过多时为站点地图索引
+#[utoipa::path(
+    get,
+    path = "/sitemap.xml",
+    tag = "sitemap",
+    responses(
+        (status = 200, description = "Every page when they fit in one sitemap, an index of sitemaps otherwise", body = String, content_type = "application/xml"),
+    )
+)]
+pub async fn get_sitemap(State(state): State<Arc<AppState>>) -> AppResult<Response> {
+    let xml = sitemap::render_root(&state.pool, &state.site_url).await?;
+    Ok(xml_response(xml))
+}
+
+// 获取分类、标签与作者页面的站点地图
+#[utoipa::path(
+    get,
+    path = "/sitemaps/pages.xml",
+    tag = "sitemap",
+    responses(
+        (status = 200, description = "The home page, categories, tags and authors", body = String, content_type = "application/xml"),
+    )
+)]
+pub async fn get_pages_sitemap(State(state): State<Arc<AppState>>) -> AppResult<Response> {
+    let xml = sitemap::render_pages(&state.pool, &state.site_url).await?;
+    Ok(xml_response(xml))
+}
+
+// 获取第 n 个文章站点地图，路径形如 /sitemaps/articles/1.xml
+#[utoipa::path(
+    get,
+    path = "/sitemaps/articles/{file}",
+    tag = "sitemap",
+    params(
+        ("file" = String, Path, description = "`<n>.xml`, the n-th 50,000 published articles"),
+    ),
+    responses(
+        (status = 200, description = "Published articles", body = String, content_type = "application/xml"),
+        (status = 404, description = "No such sitemap", body = ErrorResponse),
+    )
+)]
+pub async fn get_articles_sitemap(
+    State(state): State<Arc<AppState>>,
+    Path(file): Path<String>,
+) -> AppResult<Response> {
+    let page = file.strip_suffix(".xml").and_then(|page| page.parse().ok());
+    let xml = match page {
+        Some(page) => sitemap::render_articles(&state.pool, &state.site_url, page).await?,
+        None => None,
+    };
+    match xml {
+        Some(xml) => Ok(xml_response(xml)),
+        None => Err(Error::NotFound(String::from("sitemap"))),
+    }
+}
+
+fn xml_response(xml: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/xml; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        xml,
+    )
+        .into_response()
+}
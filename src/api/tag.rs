@@ -1,28 +1,55 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, Query, State},
-    routing::get,
-    Json, Router,
+    extract::State,
+    routing::{get, post},
+    Router,
 };
 use serde_json::Value;
 
-use super::{ApiResponse, Pagination};
+use super::{
+    article,
+    extract::{Json, Path, Query},
+    ApiResponse, Pagination,
+};
 use crate::{
     errors::{AppResult, Error},
-    models::tag::{Tag, TagData},
+    models::{
+        article::{Article, ArticleFilter},
+        article_tag::ArticleTag,
+        tag::{Tag, TagData},
+    },
     router::AppState,
-    utils::jwt::Claims,
+    utils::{
+        jwt::Claims,
+        permission::{Moderate, RequirePermission, Write},
+    },
 };
 
 pub fn create_route() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_tags).post(create_tag))
+        .route("/trash", get(get_trashed_tags))
         .route("/:id", get(get_tag).put(update_tag).delete(delete_tag))
+        .route("/:id/articles", get(get_tag_articles))
+        .route("/:id/restore", post(restore_tag))
 }
 
 // 注册新标签
+#[utoipa::path(
+    post,
+    path = "/api/tags",
+    tag = "tags",
+    responses(
+        (status = 200, description = "The new tag", body = ApiTag),
+        (status = 403, description = "Writers only", body = ErrorResponse),
+        (status = 409, description = "The tag name is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn create_tag(
+    _perm: RequirePermission<Write>,
     State(state): State<Arc<AppState>>,
     Json(tag_info): Json<TagData>,
 ) -> AppResult<Json<Value>> {
@@ -32,6 +59,7 @@ pub async fn create_tag(
             "tagname or email has already been used",
         )));
     }
+    check_trash(&state, &tag_info.name).await?;
 
     let uid = Tag::create(&state.pool, &tag_info).await?;
     let new_tag = Tag::find_by_id(&state.pool, uid as i32).await?;
@@ -45,6 +73,16 @@ pub async fn create_tag(
 }
 
 // 获取标签列表
+#[utoipa::path(
+    get,
+    path = "/api/tags",
+    tag = "tags",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Tags", body = ApiTagPage),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn get_tags(
     _claims: Claims,
     State(state): State<Arc<AppState>>,
@@ -57,6 +95,16 @@ pub async fn get_tags(
 }
 
 // 获取指定标签
+#[utoipa::path(
+    get,
+    path = "/api/tags/{id}",
+    tag = "tags",
+    responses(
+        (status = 200, description = "The tag", body = ApiTag),
+        (status = 404, description = "No such tag", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn get_tag(
     _claims: Claims,
     State(state): State<Arc<AppState>>,
@@ -72,9 +120,55 @@ pub async fn get_tag(
     Ok(Json(serde_json::json!(resp)))
 }
 
+// 获取指定标签下的文章列表
+#[utoipa::path(
+    get,
+    path = "/api/tags/{id}/articles",
+    tag = "tags",
+    params(Pagination, ArticleFilter),
+    responses(
+        (status = 200, description = "Articles of the tag the caller can see", body = ApiArticlePage),
+        (status = 404, description = "No such tag", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_tag_articles(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Query(pagination): Query<Pagination>,
+    Query(mut filter): Query<ArticleFilter>,
+) -> AppResult<Json<Value>> {
+    if Tag::find_by_id(&state.pool, id).await?.is_none() {
+        return Err(Error::NotFound(String::from("tag")));
+    }
+
+    filter.tag_id = Some(id);
+    filter.visibility = article::visibility(&state, Some(&claims)).await?;
+    let mut articles =
+        Article::find_list(&state.pool, &filter, Some(claims.user.id), &pagination).await?;
+    article::lock_protected(filter.visibility, &mut articles.list);
+
+    let resp = ApiResponse::new(articles);
+    Ok(Json(serde_json::json!(resp)))
+}
+
 // 更新指定标签的信息
+#[utoipa::path(
+    put,
+    path = "/api/tags/{id}",
+    tag = "tags",
+    responses(
+        (status = 200, description = "The updated tag", body = ApiTag),
+        (status = 403, description = "Moderators only", body = ErrorResponse),
+        (status = 404, description = "No such tag", body = ErrorResponse),
+        (status = 409, description = "The tag name is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_tag(
-    _claims: Claims,
+    _perm: RequirePermission<Moderate>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
     Json(tag_info): Json<TagData>,
@@ -85,11 +179,13 @@ pub async fn update_tag(
             "tagname or email has already been used",
         )));
     }
+    check_trash(&state, &tag_info.name).await?;
 
     let update_ok = Tag::update(&state.pool, id, &tag_info).await?;
     if !update_ok {
         return Err(Error::NotFound(String::from("tag")));
     }
+    reindex_articles(&state, id).await?;
 
     let tag = Tag::find_by_id(&state.pool, id).await?;
     if tag.is_none() {
@@ -102,12 +198,102 @@ pub async fn update_tag(
 }
 
 // 删除指定标签
+#[utoipa::path(
+    delete,
+    path = "/api/tags/{id}",
+    tag = "tags",
+    responses(
+        (status = 200, description = "Moved to the trash", body = ApiEmpty),
+        (status = 403, description = "Moderators only", body = ErrorResponse),
+        (status = 404, description = "No such tag", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_tag(
-    _claims: Claims,
+    _perm: RequirePermission<Moderate>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
 ) -> AppResult<Json<Value>> {
-    Tag::delete(&state.pool, id).await?;
+    if !Tag::delete(&state.pool, id).await? {
+        return Err(Error::NotFound(String::from("tag")));
+    }
+    reindex_articles(&state, id).await?;
+
     let resp = ApiResponse::new(());
     Ok(Json(serde_json::json!(resp)))
 }
+
+// 获取回收站中的标签列表
+#[utoipa::path(
+    get,
+    path = "/api/tags/trash",
+    tag = "tags",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Deleted tags, latest deleted first", body = ApiTrashedTagPage),
+        (status = 403, description = "Moderators only", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_trashed_tags(
+    _perm: RequirePermission<Moderate>,
+    State(state): State<Arc<AppState>>,
+    Query(pagination): Query<Pagination>,
+) -> AppResult<Json<Value>> {
+    let tags = Tag::find_trash(&state.pool, &pagination).await?;
+
+    let resp = ApiResponse::new(tags);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 从回收站恢复指定标签
+#[utoipa::path(
+    post,
+    path = "/api/tags/{id}/restore",
+    tag = "tags",
+    responses(
+        (status = 200, description = "The restored tag", body = ApiTag),
+        (status = 403, description = "Moderators only", body = ErrorResponse),
+        (status = 404, description = "No such tag in the trash", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_tag(
+    _perm: RequirePermission<Moderate>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> AppResult<Json<Value>> {
+    if !Tag::restore(&state.pool, id).await? {
+        return Err(Error::NotFound(String::from("tag")));
+    }
+    reindex_articles(&state, id).await?;
+
+    let tag = Tag::find_by_id(&state.pool, id).await?;
+    if tag.is_none() {
+        return Err(Error::NotFound(String::from("tag")));
+    }
+
+    let tag = tag.unwrap();
+    let resp = ApiResponse::new(tag);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+/// Names of deleted tags stay taken until they are purged
+async fn check_trash(state: &AppState, name: &str) -> AppResult<()> {
+    if Tag::exists_in_trash(&state.pool, name).await? {
+        return Err(Error::ObjectConflict(String::from(
+            "tag name is held by a deleted tag",
+        )));
+    }
+    Ok(())
+}
+
+/// Articles are indexed along with their tags, and the periodic sync of the index only
+/// notices edited articles
+async fn reindex_articles(state: &AppState, id: i32) -> AppResult<()> {
+    let ids = ArticleTag::find_article_ids(&state.pool, id).await?;
+    if !ids.is_empty() {
+        article::reindex(state, &ids).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+
+use crate::router::AppState;
+
+pub fn create_route() -> Router<Arc<AppState>> {
+    Router::new().route("/highlight.css", get(get_highlight_css))
+}
+
+// 获取代码高亮主题样式
+#[utoipa::path(
+    get,
+    path = "/api/themes/highlight.css",
+    tag = "themes",
+    responses(
+        (status = 200, description = "Styles of the highlighted code blocks in article html", body = String, content_type = "text/css"),
+    )
+)]
+pub async fn get_highlight_css(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        state.highlight_css.clone(),
+    )
+}
@@ -1,18 +1,29 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, Query, State},
-    routing::{get, put},
-    Json, Router,
+    extract::State,
+    routing::{get, post, put},
+    Router,
 };
 use serde_json::Value;
+use validator::{ValidationError, ValidationErrors};
 
-use super::{ApiResponse, Pagination};
+use super::{
+    extract::{Json, Path, Query},
+    ApiResponse, Pagination,
+};
 use crate::{
     errors::{AppResult, Error},
-    models::user::{CreateUser, UpdateUser, User},
+    models::{
+        token::RefreshToken,
+        user::{CreateUser, UpdatePassword, UpdateUser, User},
+    },
     router::AppState,
-    utils::jwt::Claims,
+    utils::{
+        hash::verify_password,
+        jwt::Claims,
+        permission::{Admin, RequirePermission},
+    },
 };
 
 pub fn create_route() -> Router<Arc<AppState>> {
@@ -20,10 +31,23 @@ pub fn create_route() -> Router<Arc<AppState>> {
         .route("/", get(get_users).post(create_user))
         .route("/profile", get(get_user_profile))
         .route("/edit", put(edit_user_profile))
+        .route("/password", put(change_password))
+        .route("/trash", get(get_trashed_users))
         .route("/:id", get(get_user).put(update_user).delete(delete_user))
+        .route("/:id/restore", post(restore_user))
 }
 
 // 注册新用户
+#[utoipa::path(
+    post,
+    path = "/api/users",
+    tag = "users",
+    responses(
+        (status = 200, description = "Registered, a verification mail is sent", body = ApiUser),
+        (status = 409, description = "The name or email is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    )
+)]
 pub async fn create_user(
     State(state): State<Arc<AppState>>,
     Json(user_info): Json<CreateUser>,
@@ -35,6 +59,7 @@ pub async fn create_user(
             "username or email has already been used",
         )));
     }
+    check_trash(&state, &user_info.name, &user_info.email).await?;
 
     let uid = User::create(&state.pool, &user_info).await?;
     let new_user = User::find_by_id(&state.pool, uid as i32).await?;
@@ -43,13 +68,29 @@ pub async fn create_user(
     }
 
     let new_user = new_user.unwrap();
+    // signup succeeds even if the mail fails, the user can ask to resend it
+    if let Err(err) = super::auth::send_verification(&state, &new_user).await {
+        tracing::error!("send verification mail to {}: {:?}", new_user.email, err);
+    }
+
     let resp = ApiResponse::new(new_user);
     Ok(Json(serde_json::json!(resp)))
 }
 
 // 获取用户列表
+#[utoipa::path(
+    get,
+    path = "/api/users",
+    tag = "users",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Users, newest first", body = ApiUserPage),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn get_users(
-    _claims: Claims,
+    _perm: RequirePermission<Admin>,
     State(state): State<Arc<AppState>>,
     Query(pagination): Query<Pagination>,
 ) -> AppResult<Json<Value>> {
@@ -60,6 +101,16 @@ pub async fn get_users(
 }
 
 // 获取指定用户
+#[utoipa::path(
+    get,
+    path = "/api/users/{id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "The user", body = ApiUser),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn get_user(
     _claims: Claims,
     State(state): State<Arc<AppState>>,
@@ -76,8 +127,21 @@ pub async fn get_user(
 }
 
 // 更新指定用户的信息
+#[utoipa::path(
+    put,
+    path = "/api/users/{id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "The updated user", body = ApiUser),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+        (status = 409, description = "The name or email is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_user(
-    _claims: Claims,
+    _perm: RequirePermission<Admin>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
     Json(user_info): Json<UpdateUser>,
@@ -89,6 +153,7 @@ pub async fn update_user(
             "username or email has already been used",
         )));
     }
+    check_trash(&state, &user_info.name, &user_info.email).await?;
 
     let update_ok = User::update(&state.pool, id, &user_info).await?;
     if !update_ok {
@@ -106,17 +171,97 @@ pub async fn update_user(
 }
 
 // 删除指定用户
+#[utoipa::path(
+    delete,
+    path = "/api/users/{id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Moved to the trash, its sessions are logged out", body = ApiEmpty),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_user(
-    _claims: Claims,
+    _perm: RequirePermission<Admin>,
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
 ) -> AppResult<Json<Value>> {
-    User::delete(&state.pool, id).await?;
+    if !User::delete(&state.pool, id).await? {
+        return Err(Error::NotFound(String::from("user")));
+    }
+    // sessions of a deleted user end now
+    RefreshToken::revoke_by_user(&state.pool, id).await?;
+
     let resp = ApiResponse::new(());
     Ok(Json(serde_json::json!(resp)))
 }
 
+// 获取回收站中的用户列表
+#[utoipa::path(
+    get,
+    path = "/api/users/trash",
+    tag = "users",
+    params(Pagination),
+    responses(
+        (status = 200, description = "Deleted users, latest deleted first", body = ApiUserPage),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_trashed_users(
+    _perm: RequirePermission<Admin>,
+    State(state): State<Arc<AppState>>,
+    Query(pagination): Query<Pagination>,
+) -> AppResult<Json<Value>> {
+    let users = User::find_trash(&state.pool, &pagination).await?;
+
+    let resp = ApiResponse::new(users);
+    Ok(Json(serde_json::json!(resp)))
+}
+
+// 从回收站恢复指定用户
+#[utoipa::path(
+    post,
+    path = "/api/users/{id}/restore",
+    tag = "users",
+    responses(
+        (status = 200, description = "The restored user", body = ApiUser),
+        (status = 403, description = "Admins only", body = ErrorResponse),
+        (status = 404, description = "No such user in the trash", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_user(
+    _perm: RequirePermission<Admin>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> AppResult<Json<Value>> {
+    if !User::restore(&state.pool, id).await? {
+        return Err(Error::NotFound(String::from("user")));
+    }
+
+    let user = User::find_by_id(&state.pool, id).await?;
+    if user.is_none() {
+        return Err(Error::NotFound(String::from("user")));
+    }
+
+    let user = user.unwrap();
+    let resp = ApiResponse::new(user);
+    Ok(Json(serde_json::json!(resp)))
+}
+
 // 获取当前用户信息
+#[utoipa::path(
+    get,
+    path = "/api/users/profile",
+    tag = "users",
+    responses(
+        (status = 200, description = "The current user", body = ApiUser),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn get_user_profile(
     claims: Claims,
     State(state): State<Arc<AppState>>,
@@ -131,6 +276,17 @@ pub async fn get_user_profile(
 }
 
 // 编辑当前用户信息
+#[utoipa::path(
+    put,
+    path = "/api/users/edit",
+    tag = "users",
+    responses(
+        (status = 200, description = "The updated current user", body = ApiUser),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "Invalid fields", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn edit_user_profile(
     claims: Claims,
     State(state): State<Arc<AppState>>,
@@ -157,3 +313,52 @@ pub async fn edit_user_profile(
     let resp = ApiResponse::new(user);
     Ok(Json(serde_json::json!(resp)))
 }
+
+// 修改当前用户密码
+#[utoipa::path(
+    put,
+    path = "/api/users/password",
+    tag = "users",
+    responses(
+        (status = 200, description = "Password changed, every session is logged out", body = ApiEmpty),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "Wrong current password or invalid new one", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn change_password(
+    claims: Claims,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<UpdatePassword>,
+) -> AppResult<Json<Value>> {
+    let user = User::find_by_id(&state.pool, claims.user.id).await?;
+    if user.is_none() {
+        return Err(Error::NotFound(String::from("user")));
+    }
+
+    let user = user.unwrap();
+    if !verify_password(&payload.current_password, &user.password_hash)? {
+        let mut error = ValidationError::new("password");
+        error.message = Some("is incorrect".into());
+        let mut errors = ValidationErrors::new();
+        errors.add("current_password", error);
+        return Err(Error::Validation(errors));
+    }
+
+    // every session has to log in again with the new password
+    User::update_password(&state.pool, user.id, &payload.new_password).await?;
+    RefreshToken::revoke_by_user(&state.pool, user.id).await?;
+
+    let resp = ApiResponse::new(());
+    Ok(Json(serde_json::json!(resp)))
+}
+
+/// Names and emails of deleted users stay taken until they are purged
+async fn check_trash(state: &AppState, name: &str, email: &str) -> AppResult<()> {
+    if User::exists_in_trash(&state.pool, name, email).await? {
+        return Err(Error::ObjectConflict(String::from(
+            "username or email is held by a deleted user",
+        )));
+    }
+    Ok(())
+}
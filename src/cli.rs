@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
@@ -17,22 +19,69 @@ pub enum Commands {
     },
     /// Database manager
     Db(DbArgs),
+    /// Run background jobs, e.g. scheduled publishing, without the http server
+    Worker {
+        // Seconds between two runs of the jobs
+        #[arg(short, long)]
+        interval: Option<u64>,
+    },
+    /// Search index manager
+    Search(SearchArgs),
+    /// Write sitemap.xml and robots.txt to disk for static hosting
+    Sitemap {
+        // Directory to write the files to
+        #[arg(short, long, default_value = "public")]
+        out: PathBuf,
+    },
+    /// Print the OpenAPI document of the REST API, e.g. for client code generators
+    Openapi {
+        // Write it to this file instead of stdout
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    #[command(subcommand)]
+    pub command: SearchCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SearchCommands {
+    /// Rebuild the search index from the database
+    Reindex,
 }
 
 #[derive(Debug, Args)]
 pub struct DbArgs {
     #[command(subcommand)]
-    command: Option<DbCommands>,
+    pub command: DbCommands,
 }
 
 #[derive(Debug, Subcommand)]
-enum DbCommands {
+pub enum DbCommands {
     /// Initial database and tables
     Init,
     /// Destroy database and tables
-    Destroy,
+    Destroy {
+        // Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
     /// Create a new migrate file
-    Migrate,
+    Migrate {
+        // Migration name, e.g. add-article-index
+        name: String,
+    },
     /// Run migrate update
     Upgrade,
+    /// Revert applied migrations
+    Downgrade {
+        // Revert every migration newer than this version, defaults to the latest one only
+        #[arg(short, long)]
+        target: Option<i64>,
+    },
+    /// Show migrations status
+    Status,
 }
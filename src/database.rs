@@ -1,5 +1,23 @@
-use anyhow::Context;
-use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use sqlx::{
+    migrate::{Migrate, MigrateDatabase, Migrator},
+    mysql::MySqlPoolOptions,
+    MySql, MySqlPool,
+};
+
+use crate::cli::DbCommands;
+
+/// Migrations embedded from the `migrations` directory at compile time
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+const MIGRATIONS_DIR: &str = "migrations";
 
 pub async fn init(url: &str) -> anyhow::Result<MySqlPool> {
     MySqlPoolOptions::new()
@@ -8,3 +26,167 @@ pub async fn init(url: &str) -> anyhow::Result<MySqlPool> {
         .await
         .context("could not connect to database_url")
 }
+
+pub async fn run(command: DbCommands, url: &str) -> anyhow::Result<()> {
+    match command {
+        DbCommands::Init => {
+            create(url).await?;
+            let pool = init(url).await?;
+            upgrade(&pool).await?;
+        }
+        DbCommands::Destroy { yes } => {
+            if !yes && !confirm(&format!("Drop the database at {}?", url))? {
+                println!("Aborted");
+                return Ok(());
+            }
+            destroy(url).await?;
+        }
+        DbCommands::Migrate { name } => {
+            let (up, down) = add_migration(Path::new(MIGRATIONS_DIR), &name)?;
+            println!("Created {}", up.display());
+            println!("Created {}", down.display());
+        }
+        DbCommands::Upgrade => {
+            let pool = init(url).await?;
+            upgrade(&pool).await?;
+        }
+        DbCommands::Downgrade { target } => {
+            let pool = init(url).await?;
+            downgrade(&pool, target).await?;
+        }
+        DbCommands::Status => {
+            let pool = init(url).await?;
+            status(&pool).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn create(url: &str) -> anyhow::Result<()> {
+    if MySql::database_exists(url).await? {
+        println!("Database already exists");
+        return Ok(());
+    }
+
+    MySql::create_database(url)
+        .await
+        .context("could not create database")?;
+    println!("Database created");
+    Ok(())
+}
+
+async fn destroy(url: &str) -> anyhow::Result<()> {
+    if !MySql::database_exists(url).await? {
+        println!("Database does not exist");
+        return Ok(());
+    }
+
+    MySql::drop_database(url)
+        .await
+        .context("could not drop database")?;
+    println!("Database dropped");
+    Ok(())
+}
+
+/// Create a pair of reversible migration files named `<timestamp>_<name>.{up,down}.sql`
+fn add_migration(dir: &Path, name: &str) -> anyhow::Result<(PathBuf, PathBuf)> {
+    let name = name.trim().replace(' ', "-");
+    if name.is_empty() {
+        bail!("migration name can not be empty");
+    }
+
+    fs::create_dir_all(dir).context("could not create migrations directory")?;
+
+    let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+    let up = dir.join(format!("{}_{}.up.sql", timestamp, name));
+    let down = dir.join(format!("{}_{}.down.sql", timestamp, name));
+
+    fs::write(&up, "-- Add up migration script here\n")
+        .with_context(|| format!("could not write {}", up.display()))?;
+    fs::write(&down, "-- Add down migration script here\n")
+        .with_context(|| format!("could not write {}", down.display()))?;
+
+    Ok((up, down))
+}
+
+async fn upgrade(pool: &MySqlPool) -> anyhow::Result<()> {
+    MIGRATOR
+        .run(pool)
+        .await
+        .context("could not run migrations")?;
+    println!("Migrations applied");
+    Ok(())
+}
+
+async fn downgrade(pool: &MySqlPool, target: Option<i64>) -> anyhow::Result<()> {
+    let target = match target {
+        Some(target) => target,
+        None => {
+            // revert only the latest applied migration
+            let mut conn = pool.acquire().await?;
+            conn.ensure_migrations_table().await?;
+            let mut versions: Vec<i64> = conn
+                .list_applied_migrations()
+                .await?
+                .iter()
+                .map(|m| m.version)
+                .collect();
+            versions.sort_unstable();
+
+            if versions.pop().is_none() {
+                println!("No migration to revert");
+                return Ok(());
+            }
+            versions.pop().unwrap_or(0)
+        }
+    };
+
+    MIGRATOR
+        .undo(pool, target)
+        .await
+        .context("could not revert migrations")?;
+    println!("Migrations reverted to version {}", target);
+    Ok(())
+}
+
+async fn status(pool: &MySqlPool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let applied: HashMap<_, _> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum))
+        .collect();
+
+    for migration in MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+    {
+        let state = match applied.get(&migration.version) {
+            Some(checksum) if *checksum == migration.checksum => "installed",
+            Some(_) => "installed (different checksum)",
+            None => "pending",
+        };
+        println!("{}/{} {}", migration.version, state, migration.description);
+    }
+
+    for version in applied.keys() {
+        if !MIGRATOR.iter().any(|m| m.version == *version) {
+            println!("{}/missing (applied but not found in binary)", version);
+        }
+    }
+
+    Ok(())
+}
+
+fn confirm(prompt: &str) -> anyhow::Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim(), "y" | "Y" | "yes"))
+}
@@ -1,12 +1,27 @@
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
 use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Serialize;
+use utoipa::ToSchema;
+use validator::ValidationErrors;
 
 pub type AppResult<T> = Result<T, Error>;
 
+/// Whether internal error details (database, hashing) are exposed to clients
+static DEBUG: AtomicBool = AtomicBool::new(false);
+
+pub fn set_debug(debug: bool) {
+    DEBUG.store(debug, Ordering::Relaxed);
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("{0}")]
@@ -20,13 +35,32 @@ pub enum Error {
     #[error("{0} not found")]
     NotFound(String),
 
-    // #[error("{0}")]
-    // BadRequest(String),
+    #[error("{0}")]
+    BadRequest(String),
+
     #[error("{0}")]
     ObjectConflict(String),
 
     #[error("hash password")]
     HashPassword(#[from] argon2::password_hash::Error),
+
+    #[error("Permission denied")]
+    Forbidden,
+
+    #[error("Validation failed")]
+    Validation(#[from] ValidationErrors),
+
+    #[error("Account is not activated")]
+    InactiveAccount,
+
+    #[error("{0}")]
+    TooManyRequests(String),
+
+    #[error("send mail: {0}")]
+    Mail(String),
+
+    #[error("search index: {0}")]
+    Search(#[from] tantivy::TantivyError),
 }
 
 impl Error {
@@ -34,21 +68,175 @@ impl Error {
         match self {
             Error::Database(_) => 1001,
             // Error::ReadContext => 1002,
+            Error::Mail(_) => 1003,
+            Error::Search(_) => 1004,
             Error::Auth(_) => 2001,
             Error::NotFound(_) => 2002,
-            // Error::BadRequest(_) => 2003,
+            Error::BadRequest(_) => 2003,
             Error::ObjectConflict(_) => 2004,
             Error::HashPassword(_) => 2005,
+            Error::Forbidden => 2006,
+            Error::Validation(_) => 2007,
+            Error::InactiveAccount => 2008,
+            Error::TooManyRequests(_) => 2009,
         }
     }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::Database(_) | Error::HashPassword(_) | Error::Mail(_) | Error::Search(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Error::Auth(AuthError::TokenCreation) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Auth(_) => StatusCode::UNAUTHORIZED,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::ObjectConflict(_) => StatusCode::CONFLICT,
+            Error::Forbidden => StatusCode::FORBIDDEN,
+            Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::InactiveAccount => StatusCode::FORBIDDEN,
+            Error::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    /// One error of each kind with what it means, to document `code` and `status` for
+    /// API clients
+    pub fn catalog() -> Vec<(Error, &'static str)> {
+        vec![
+            (
+                Error::Database(sqlx::Error::PoolTimedOut),
+                "database failure",
+            ),
+            (Error::Mail(String::new()), "the mail could not be sent"),
+            (
+                Error::Search(tantivy::TantivyError::InvalidArgument(String::new())),
+                "search index failure",
+            ),
+            (
+                Error::Auth(AuthError::InvalidToken),
+                "missing, wrong, expired or revoked credentials",
+            ),
+            (
+                Error::NotFound(String::new()),
+                "the object does not exist or is hidden from the caller",
+            ),
+            (
+                Error::BadRequest(String::new()),
+                "malformed request or forbidden state change",
+            ),
+            (
+                Error::ObjectConflict(String::new()),
+                "the name, email or slug is already taken",
+            ),
+            (
+                Error::HashPassword(argon2::password_hash::Error::Password),
+                "password hashing failure",
+            ),
+            (Error::Forbidden, "the caller lacks the permission"),
+            (
+                Error::Validation(ValidationErrors::new()),
+                "invalid fields, `data` lists the messages of each field",
+            ),
+            (
+                Error::InactiveAccount,
+                "the email address is not verified yet",
+            ),
+            (
+                Error::TooManyRequests(String::new()),
+                "too many attempts, try again later",
+            ),
+        ]
+    }
+
+    fn message(&self) -> String {
+        let status = self.status();
+        if status.is_server_error() && !DEBUG.load(Ordering::Relaxed) {
+            return status
+                .canonical_reason()
+                .unwrap_or("Internal Server Error")
+                .to_string();
+        }
+        self.to_string()
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        let body = Json(json!(
-            { "code": self.code(), "message": self.to_string() }
-        ));
-        (StatusCode::OK, body).into_response()
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!("{:?}", self);
+        }
+
+        let body = ErrorResponse {
+            code: self.code(),
+            message: self.message(),
+            data: match &self {
+                Error::Validation(errors) => Some(field_errors(errors)),
+                _ => None,
+            },
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+/// The body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub code: u32,
+    pub message: String,
+    // messages of each invalid field, only for validation errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub data: Option<BTreeMap<String, Vec<String>>>,
+}
+
+/// Flatten validation errors into `{ field: [message, ...] }`
+fn field_errors(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errors)| {
+            let messages = errors
+                .iter()
+                .map(|e| match &e.message {
+                    Some(message) => message.to_string(),
+                    None => format!("invalid {}", e.code),
+                })
+                .collect();
+            (field.to_string(), messages)
+        })
+        .collect()
+}
+
+/// Describe an extractor rejection together with its underlying cause
+fn rejection_message(err: &dyn std::error::Error) -> String {
+    let mut messages = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(err) = source {
+        let message = err.to_string();
+        if messages.last() != Some(&message) {
+            messages.push(message);
+        }
+        source = err.source();
+    }
+    messages.join(": ")
+}
+
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Error::BadRequest(rejection_message(&rejection))
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Error::BadRequest(rejection_message(&rejection))
+    }
+}
+
+impl From<PathRejection> for Error {
+    fn from(rejection: PathRejection) -> Self {
+        Error::BadRequest(rejection_message(&rejection))
     }
 }
 
@@ -63,3 +251,83 @@ pub enum AuthError {
     #[error("Invalid authentication token")]
     InvalidToken,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use validator::ValidationError;
+
+    use super::*;
+
+    #[test]
+    fn every_error_has_its_own_code() {
+        let catalog = Error::catalog();
+        let codes: HashSet<u32> = catalog.iter().map(|(e, _)| e.code()).collect();
+        assert_eq!(codes.len(), catalog.len());
+    }
+
+    #[test]
+    fn errors_map_to_their_status() {
+        let cases = [
+            (Error::NotFound(String::from("user")), StatusCode::NOT_FOUND),
+            (Error::BadRequest(String::new()), StatusCode::BAD_REQUEST),
+            (Error::ObjectConflict(String::new()), StatusCode::CONFLICT),
+            (Error::Forbidden, StatusCode::FORBIDDEN),
+            (Error::InactiveAccount, StatusCode::FORBIDDEN),
+            (
+                Error::Validation(ValidationErrors::new()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                Error::TooManyRequests(String::new()),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            (
+                Error::Auth(AuthError::InvalidToken),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                Error::Auth(AuthError::MissingCredentials),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                Error::Auth(AuthError::TokenCreation),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                Error::Database(sqlx::Error::PoolTimedOut),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                Error::Mail(String::new()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ];
+        for (error, status) in cases {
+            assert_eq!(error.status(), status, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn server_errors_hide_their_details() {
+        let error = Error::Mail(String::from("smtp.example.com refused"));
+        assert_eq!(error.message(), "Internal Server Error");
+
+        let error = Error::NotFound(String::from("article"));
+        assert_eq!(error.message(), "article not found");
+    }
+
+    #[test]
+    fn field_errors_list_messages_by_field() {
+        let mut errors = ValidationErrors::new();
+        let mut too_long = ValidationError::new("length");
+        too_long.message = Some("must be 1-64 characters".into());
+        errors.add("name", too_long);
+        errors.add("email", ValidationError::new("email"));
+
+        let fields = field_errors(&errors);
+        assert_eq!(fields["name"], vec!["must be 1-64 characters"]);
+        assert_eq!(fields["email"], vec!["invalid email"]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Syndication of published articles as RSS 2.0, Atom and JSON Feed documents.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde_json::json;
+
+use crate::models::article::ArticleDetail;
+
+#[derive(Debug, Clone, Copy)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+}
+
+/// Latest articles of the site, or of one of its categories, tags or authors. Protected
+/// articles are expected to be locked already, they only show their summary.
+pub struct Feed<'a> {
+    pub title: String,
+    pub description: String,
+    pub site_url: &'a str,
+    // where the feed itself is served, query included
+    pub feed_url: String,
+    pub author: String,
+    pub articles: Vec<ArticleDetail>,
+}
+
+impl Feed<'_> {
+    /// The latest publication or edit among the articles, `None` when there are none
+    pub fn last_modified(&self) -> Option<NaiveDateTime> {
+        self.articles
+            .iter()
+            .flat_map(|a| [a.article.published_at, Some(a.article.updated_at)])
+            .flatten()
+            .max()
+    }
+
+    pub fn render(&self, format: FeedFormat) -> String {
+        match format {
+            FeedFormat::Rss => self.rss(),
+            FeedFormat::Atom => self.atom(),
+            FeedFormat::Json => self.json(),
+        }
+    }
+
+    fn link(&self, article: &ArticleDetail) -> String {
+        format!("{}/articles/{}", self.site_url, article.article.slug)
+    }
+
+    fn content(article: &ArticleDetail) -> Option<&str> {
+        if article.locked {
+            None
+        } else {
+            article.article.content_html.as_deref()
+        }
+    }
+
+    fn rss(&self) -> String {
+        let items = self
+            .articles
+            .iter()
+            .map(|a| rss::Item {
+                title: Some(a.article.title.clone()),
+                link: Some(self.link(a)),
+                description: a.article.summary.clone(),
+                content: Self::content(a).map(String::from),
+                categories: a
+                    .tags
+                    .iter()
+                    .map(|t| rss::Category {
+                        name: t.name.clone(),
+                        domain: None,
+                    })
+                    .collect(),
+                guid: Some(rss::Guid {
+                    value: self.link(a),
+                    permalink: true,
+                }),
+                pub_date: a.article.published_at.map(|at| utc(at).to_rfc2822()),
+                ..Default::default()
+            })
+            .collect();
+
+        let channel = rss::Channel {
+            title: self.title.clone(),
+            link: self.site_url.to_string(),
+            description: self.description.clone(),
+            last_build_date: self.last_modified().map(|at| utc(at).to_rfc2822()),
+            generator: Some(String::from("vars")),
+            items,
+            ..Default::default()
+        };
+
+        channel.to_string()
+    }
+
+    fn atom(&self) -> String {
+        use atom_syndication::{Content, Entry, Link, Person, Text};
+
+        let entries = self
+            .articles
+            .iter()
+            .map(|a| Entry {
+                title: Text::plain(a.article.title.clone()),
+                id: self.link(a),
+                updated: utc(a.article.updated_at).into(),
+                links: vec![Link {
+                    href: self.link(a),
+                    rel: String::from("alternate"),
+                    ..Default::default()
+                }],
+                categories: a
+                    .tags
+                    .iter()
+                    .map(|t| atom_syndication::Category {
+                        term: t.name.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                published: a.article.published_at.map(|at| utc(at).into()),
+                summary: a.article.summary.clone().map(Text::plain),
+                content: Self::content(a).map(|html| Content {
+                    value: Some(html.to_string()),
+                    content_type: Some(String::from("html")),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect();
+
+        let feed = atom_syndication::Feed {
+            title: Text::plain(self.title.clone()),
+            id: self.feed_url.clone(),
+            // the epoch stands for a feed which was never updated
+            updated: self.last_modified().map_or_else(
+                || Utc.timestamp_opt(0, 0).unwrap().into(),
+                |at| utc(at).into(),
+            ),
+            authors: vec![Person {
+                name: self.author.clone(),
+                ..Default::default()
+            }],
+            links: vec![
+                Link {
+                    href: self.site_url.to_string(),
+                    rel: String::from("alternate"),
+                    ..Default::default()
+                },
+                Link {
+                    href: self.feed_url.clone(),
+                    rel: String::from("self"),
+                    ..Default::default()
+                },
+            ],
+            subtitle: Some(Text::plain(self.description.clone())),
+            entries,
+            ..Default::default()
+        };
+
+        feed.to_string()
+    }
+
+    fn json(&self) -> String {
+        let items: Vec<_> = self
+            .articles
+            .iter()
+            .map(|a| {
+                let mut item = json!({
+                    "id": self.link(a),
+                    "url": self.link(a),
+                    "title": a.article.title,
+                    "summary": a.article.summary,
+                    "date_published": a.article.published_at.map(|at| utc(at).to_rfc3339()),
+                    "date_modified": utc(a.article.updated_at).to_rfc3339(),
+                    "tags": a.tags.iter().map(|t| &t.name).collect::<Vec<_>>(),
+                });
+                // every item needs a content, locked ones fall back to their summary
+                match Self::content(a) {
+                    Some(html) => item["content_html"] = json!(html),
+                    None => {
+                        item["content_text"] =
+                            json!(a.article.summary.as_deref().unwrap_or_default())
+                    }
+                }
+                item
+            })
+            .collect();
+
+        let feed = json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "home_page_url": self.site_url,
+            "feed_url": self.feed_url,
+            "description": self.description,
+            "authors": [{ "name": self.author }],
+            "items": items,
+        });
+
+        feed.to_string()
+    }
+}
+
+/// Times are stored in UTC
+pub fn utc(at: NaiveDateTime) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&at)
+}
@@ -0,0 +1,133 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use axum::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::{
+    errors::{AppResult, Error},
+    settings,
+};
+
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> AppResult<()>;
+}
+
+pub fn init(settings: &settings::Mail) -> anyhow::Result<Arc<dyn Mailer>> {
+    let from: Mailbox = settings.from.parse().context("invalid mail.from address")?;
+
+    let mailer: Arc<dyn Mailer> = match settings.backend.as_str() {
+        "smtp" => {
+            let builder = if settings.smtp_starttls {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.smtp_host)
+                    .context("invalid mail.smtp_host")?
+            } else {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.smtp_host)
+            };
+            let mut builder = builder.port(settings.smtp_port);
+            if !settings.smtp_username.is_empty() {
+                builder = builder.credentials(Credentials::new(
+                    settings.smtp_username.clone(),
+                    settings.smtp_password.clone(),
+                ));
+            }
+            Arc::new(SmtpMailer {
+                from,
+                transport: builder.build(),
+            })
+        }
+        "file" => {
+            fs::create_dir_all(&settings.dir).context("could not create mail.dir")?;
+            Arc::new(FileMailer {
+                from,
+                dir: PathBuf::from(&settings.dir),
+            })
+        }
+        "log" => Arc::new(LogMailer),
+        backend => anyhow::bail!("unknown mail backend: {}", backend),
+    };
+
+    Ok(mailer)
+}
+
+fn build_message(from: &Mailbox, mail: Mail) -> AppResult<Message> {
+    let to: Mailbox = mail
+        .to
+        .parse()
+        .map_err(|_| Error::BadRequest(format!("invalid email address: {}", mail.to)))?;
+
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(mail.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(mail.body)
+        .map_err(|e| Error::Mail(e.to_string()))
+}
+
+/// Deliver mails through an SMTP relay
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> AppResult<()> {
+        let message = build_message(&self.from, mail)?;
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| Error::Mail(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Write every mail as an `.eml` file into a directory, for local development and tests
+pub struct FileMailer {
+    from: Mailbox,
+    dir: PathBuf,
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> AppResult<()> {
+        let name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%d%H%M%S%3f"),
+            mail.to.replace(['/', '\\'], "_")
+        );
+        let message = build_message(&self.from, mail)?;
+        tokio::fs::write(self.dir.join(name), message.formatted())
+            .await
+            .map_err(|e| Error::Mail(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Only log mails, nothing is delivered
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> AppResult<()> {
+        tracing::info!(
+            "mail to {}, subject: {}\n{}",
+            mail.to,
+            mail.subject,
+            mail.body
+        );
+        Ok(())
+    }
+}
@@ -2,16 +2,25 @@ mod api;
 mod cli;
 mod database;
 mod errors;
+mod feed;
 mod logger;
+mod mailer;
 mod models;
 mod router;
+mod search;
 mod settings;
+mod sitemap;
 mod utils;
+mod views;
+mod worker;
+
+use std::sync::Arc;
 
 use anyhow::Context;
 use clap::Parser;
-use cli::Commands;
+use cli::{Commands, SearchCommands};
 use dotenvy::dotenv;
+use utoipa::OpenApi;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -21,17 +30,54 @@ async fn main() -> anyhow::Result<()> {
     dotenv().context(".env file not found")?;
 
     let mut settings = settings::init()?;
-    let pool = database::init(&settings.database.url).await?;
     logger::init(&settings.logger.level);
 
     match args.command {
         Some(Commands::Server { port }) => {
             settings.server.port = port.unwrap_or(settings.server.port);
-            router::serve(settings, pool).await;
+            let pool = database::init(&settings.database.url).await?;
+            router::serve(settings, pool).await?;
+        }
+        Some(Commands::Db(args)) => {
+            database::run(args.command, &settings.database.url).await?;
+        }
+        Some(Commands::Worker { interval }) => {
+            let pool = database::init(&settings.database.url).await?;
+            let interval = interval.unwrap_or(settings.worker.interval);
+            worker::run(pool, interval, settings.trash.retention).await;
+        }
+        Some(Commands::Search(args)) => match args.command {
+            SearchCommands::Reindex => {
+                let pool = database::init(&settings.database.url).await?;
+                let index = Arc::new(
+                    search::SearchIndex::open(&settings.search.dir)
+                        .context("open search index, stop the server first")?,
+                );
+                index.rebuild(&pool).await?;
+                println!("Search index rebuilt");
+            }
+        },
+        Some(Commands::Sitemap { out }) => {
+            let pool = database::init(&settings.database.url).await?;
+            let files =
+                sitemap::write(&pool, &settings.site.url, &settings.robots.disallow, &out).await?;
+            for file in files {
+                println!("Written {}", file.display());
+            }
+        }
+        Some(Commands::Openapi { out }) => {
+            let document = api::openapi::ApiDoc::openapi().to_pretty_json()?;
+            match out {
+                Some(out) => {
+                    std::fs::write(&out, document)?;
+                    println!("Written {}", out.display());
+                }
+                None => println!("{}", document),
+            }
         }
-        Some(Commands::Db(_cmd)) => {}
         None => {
-            router::serve(settings, pool).await;
+            let pool = database::init(&settings.database.url).await?;
+            router::serve(settings, pool).await?;
         }
     }
 
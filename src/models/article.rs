@@ -1,25 +1,133 @@
-use chrono::NaiveDateTime;
+use std::fmt::Debug;
+
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, MySqlPool};
+use sqlx::{encode::Encode, FromRow, MySql, MySqlPool, QueryBuilder, Transaction, Type};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 use crate::{
-    api::{Pagination, PaginationResponse},
+    api::{Pagination, PaginationResponse, SortOrder},
     errors::AppResult,
+    models::{
+        article_tag::ArticleTag,
+        category::{Category, CategorySummary},
+        like::{Like, LikeTarget},
+        revision::{ArticleRevision, PublicRevision},
+        slug_redirect::SlugRedirect,
+        tag::TagSummary,
+    },
+    utils::{
+        hash::generate_hash,
+        markdown::{self, Outline},
+    },
 };
 
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i8)]
+pub enum ArticleStatus {
+    #[default]
+    Draft = 0,
+    Pending = 1,
+    Published = 2,
+    // published by the worker once `published_at` is reached
+    Scheduled = 3,
+    Archived = 4,
+}
+
+impl ArticleStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ArticleStatus::Draft => "draft",
+            ArticleStatus::Pending => "pending",
+            ArticleStatus::Published => "published",
+            ArticleStatus::Scheduled => "scheduled",
+            ArticleStatus::Archived => "archived",
+        }
+    }
+
+    /// Whether the workflow allows moving an article from `self` to `to`
+    pub fn can_transition_to(self, to: ArticleStatus) -> bool {
+        use ArticleStatus::*;
+
+        matches!(
+            (self, to),
+            (Draft, Pending | Published | Scheduled)
+                | (Pending, Draft | Published | Scheduled)
+                | (Scheduled, Draft | Published | Scheduled)
+                | (Published, Draft | Archived)
+                | (Archived, Draft | Published)
+        )
+    }
+
+    /// Whether the workflow lets a user, a moderator or not, move an article from `self`
+    /// to `to`. Only moderators take articles out of review, otherwise authors could send
+    /// theirs back to draft and publish it from there.
+    pub fn can_be_moved_to(self, to: ArticleStatus, moderator: bool) -> bool {
+        self.can_transition_to(to) && (moderator || self != ArticleStatus::Pending)
+    }
+}
+
+/// Which unpublished articles a caller may see
+#[derive(Debug, Default, Clone, Copy)]
+pub enum Visibility {
+    // published articles only
+    #[default]
+    Public,
+    // published articles and the ones written by this user
+    Author(i32),
+    All,
+}
+
+impl Visibility {
+    pub fn can_see(self, article: &PublicArticle) -> bool {
+        match self {
+            Visibility::Public => article.status == ArticleStatus::Published,
+            Visibility::Author(user_id) => {
+                article.status == ArticleStatus::Published || article.user_id == user_id
+            }
+            Visibility::All => true,
+        }
+    }
+
+    /// Whether the caller wrote the article or moderates it, so needs no password to read it
+    pub fn owns(self, article: &PublicArticle) -> bool {
+        match self {
+            Visibility::Public => false,
+            Visibility::Author(user_id) => article.user_id == user_id,
+            Visibility::All => true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ArticleStatusData {
+    pub status: ArticleStatus,
+    // required when scheduling
+    pub published_at: Option<NaiveDateTime>,
+    // archive the article automatically at this time
+    pub unpublish_at: Option<NaiveDateTime>,
+}
+
 #[derive(FromRow)]
 pub struct Article {
     pub id: i32,
     pub title: String,
-    pub slug: Option<String>,
+    pub slug: String,
     pub content: String,
+    pub content_html: Option<String>,
     pub summary: Option<String>,
     pub cover: Option<String>,
-    pub status: i8,
-    pub password: Option<String>,
+    pub status: ArticleStatus,
+    pub password_hash: Option<String>,
     pub read_count: i32,
     pub like_count: i32,
     pub is_top: bool,
+    pub published_at: Option<NaiveDateTime>,
+    pub unpublish_at: Option<NaiveDateTime>,
     pub category_id: i32,
     pub user_id: i32,
     pub created_at: Option<NaiveDateTime>,
@@ -27,80 +135,333 @@ pub struct Article {
     pub deleted_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct CreateArticle {
+    #[validate(length(min = 1, max = 256, message = "must be 1-256 characters"))]
     pub title: String,
+    // generated from the title when absent
+    #[validate(length(max = 100, message = "must be at most 100 characters"))]
     pub slug: Option<String>,
+    #[validate(length(min = 1, message = "can not be empty"))]
     pub content: String,
+    #[validate(length(max = 256, message = "must be at most 256 characters"))]
     pub summary: Option<String>,
+    #[validate(length(max = 64, message = "must be at most 64 characters"))]
     pub cover: Option<String>,
-    pub status: i8,
+    #[serde(default)]
+    pub status: ArticleStatus,
+    // required when scheduling
+    pub published_at: Option<NaiveDateTime>,
+    // archive the article automatically at this time
+    pub unpublish_at: Option<NaiveDateTime>,
+    // readers have to unlock the content with it, stored hashed
+    #[validate(length(min = 1, max = 64, message = "must be 1-64 characters"))]
     pub password: Option<String>,
     pub category_id: i32,
+    #[serde(default)]
+    pub tag_ids: Vec<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct PreviewArticle {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UnlockArticle {
+    #[validate(length(min = 1, max = 64, message = "must be 1-64 characters"))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct UpdateArticle {
+    #[validate(length(min = 1, max = 256, message = "must be 1-256 characters"))]
     pub title: String,
+    // the slug is left untouched when absent, a former slug keeps redirecting here
+    #[validate(length(max = 100, message = "must be at most 100 characters"))]
     pub slug: Option<String>,
+    #[validate(length(min = 1, message = "can not be empty"))]
     pub content: String,
+    #[validate(length(max = 256, message = "must be at most 256 characters"))]
     pub summary: Option<String>,
+    #[validate(length(max = 64, message = "must be at most 64 characters"))]
     pub cover: Option<String>,
-    pub status: i8,
-    pub read_count: i32,
-    pub like_count: i32,
     pub is_top: bool,
+    // the password is left untouched when absent and removed when empty
+    #[validate(length(max = 64, message = "must be at most 64 characters"))]
     pub password: Option<String>,
     pub category_id: i32,
+    // tags are left untouched when absent
+    pub tag_ids: Option<Vec<i32>>,
 }
 
-#[derive(Debug, Serialize, FromRow)]
+#[derive(Debug, Serialize, FromRow, ToSchema)]
 pub struct PublicArticle {
     pub id: i32,
     pub title: String,
-    pub slug: Option<String>,
+    pub slug: String,
     pub content: String,
+    pub content_html: Option<String>,
     pub summary: Option<String>,
     pub cover: Option<String>,
-    pub status: i8,
+    pub status: ArticleStatus,
     pub read_count: i32,
     pub like_count: i32,
     pub is_top: i8,
+    pub published_at: Option<NaiveDateTime>,
+    pub unpublish_at: Option<NaiveDateTime>,
     pub category_id: i32,
     pub user_id: i32,
+    pub comment_count: i64,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+}
+
+/// Query parameters narrowing and ordering an article list
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct ArticleFilter {
+    pub category_id: Option<i32>,
+    // with `category_id`, articles of its subcategories too
+    pub include_descendants: Option<bool>,
+    pub tag_id: Option<i32>,
+    // tag name
+    pub tag: Option<String>,
+    pub user_id: Option<i32>,
+    pub status: Option<ArticleStatus>,
+    // creation date range, both ends inclusive
+    pub created_from: Option<NaiveDate>,
+    pub created_to: Option<NaiveDate>,
+    // pinned articles come first unless disabled
+    pub top_first: Option<bool>,
+    pub sort: Option<ArticleSort>,
+    pub order: Option<SortOrder>,
+    #[serde(skip)]
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArticleSort {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    PublishedAt,
+    ReadCount,
+    LikeCount,
+}
+
+impl ArticleSort {
+    fn column(self) -> &'static str {
+        match self {
+            ArticleSort::CreatedAt => "a.created_at",
+            ArticleSort::UpdatedAt => "a.updated_at",
+            ArticleSort::PublishedAt => "a.published_at",
+            ArticleSort::ReadCount => "a.read_count",
+            ArticleSort::LikeCount => "a.like_count",
+        }
+    }
+}
+
+/// What `ArticleFilter::push_where` writes to, a query builder, or in tests a recorder of
+/// the bound values
+trait WhereClause<'q> {
+    fn push(&mut self, sql: &str) -> &mut Self;
+
+    fn push_bind<T>(&mut self, value: T) -> &mut Self
+    where
+        T: 'q + Send + Encode<'q, MySql> + Type<MySql> + Debug;
+}
+
+impl<'q> WhereClause<'q> for QueryBuilder<'q, MySql> {
+    fn push(&mut self, sql: &str) -> &mut Self {
+        QueryBuilder::push(self, sql)
+    }
+
+    fn push_bind<T>(&mut self, value: T) -> &mut Self
+    where
+        T: 'q + Send + Encode<'q, MySql> + Type<MySql> + Debug,
+    {
+        QueryBuilder::push_bind(self, value)
+    }
+}
+
+impl ArticleFilter {
+    /// Append the `WHERE` clause, the article table must be aliased as `a`.
+    /// Articles in the trash are left out.
+    fn push_where<'q>(&self, builder: &mut impl WhereClause<'q>) {
+        builder.push(" WHERE a.deleted_at IS NULL");
+        match self.visibility {
+            Visibility::Public => {
+                builder
+                    .push(" AND a.status = ")
+                    .push_bind(ArticleStatus::Published);
+            }
+            Visibility::Author(user_id) => {
+                builder
+                    .push(" AND (a.status = ")
+                    .push_bind(ArticleStatus::Published)
+                    .push(" OR a.user_id = ")
+                    .push_bind(user_id)
+                    .push(")");
+            }
+            Visibility::All => {}
+        }
+        if let Some(category_id) = self.category_id {
+            if self.include_descendants.unwrap_or(false) {
+                builder
+                    .push(" AND a.category_id IN (WITH RECURSIVE sub AS (SELECT id FROM category WHERE id = ")
+                    .push_bind(category_id)
+                    .push(" UNION ALL SELECT c.id FROM category c INNER JOIN sub ON c.parent_id = sub.id WHERE c.deleted_at IS NULL) SELECT id FROM sub)");
+            } else {
+                builder.push(" AND a.category_id = ").push_bind(category_id);
+            }
+        }
+        if let Some(tag_id) = self.tag_id {
+            builder
+                .push(" AND EXISTS (SELECT 1 FROM article_tag at WHERE at.article_id = a.id AND at.tag_id = ")
+                .push_bind(tag_id)
+                .push(")");
+        }
+        if let Some(tag) = &self.tag {
+            builder
+                .push(" AND EXISTS (SELECT 1 FROM article_tag at INNER JOIN tag t ON t.id = at.tag_id WHERE at.article_id = a.id AND t.deleted_at IS NULL AND t.name = ")
+                .push_bind(tag.clone())
+                .push(")");
+        }
+        if let Some(user_id) = self.user_id {
+            builder.push(" AND a.user_id = ").push_bind(user_id);
+        }
+        if let Some(status) = self.status {
+            builder.push(" AND a.status = ").push_bind(status);
+        }
+        if let Some(from) = self.created_from {
+            builder
+                .push(" AND a.created_at >= ")
+                .push_bind(from.and_hms_opt(0, 0, 0));
+        }
+        if let Some(to) = self.created_to.and_then(|d| d.succ_opt()) {
+            builder
+                .push(" AND a.created_at < ")
+                .push_bind(to.and_hms_opt(0, 0, 0));
+        }
+    }
+
+    fn push_order_by(&self, builder: &mut QueryBuilder<'_, MySql>) {
+        builder.push(" ORDER BY ");
+        if self.top_first.unwrap_or(true) {
+            builder.push("a.is_top DESC, ");
+        }
+        let sort = self.sort.unwrap_or_default();
+        let order = self.order.unwrap_or_default();
+        // keep pages stable when the sort column has ties
+        builder.push(format!(
+            "{} {}, a.id {}",
+            sort.column(),
+            order.keyword(),
+            order.keyword()
+        ));
+    }
+}
+
+/// An article in the trash
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct TrashedArticle {
+    pub id: i32,
+    pub title: String,
+    pub slug: String,
+    pub status: ArticleStatus,
+    pub category_id: i32,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub deleted_at: NaiveDateTime,
+}
+
+/// An article with its tags, and the outline of its content when requested alone
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ArticleDetail {
+    #[serde(flatten)]
+    pub article: PublicArticle,
+    pub tags: Vec<TagSummary>,
+    // its category and the ancestors of it, the top-level one first
+    pub category_path: Vec<CategorySummary>,
+    // whether the caller liked it, absent for anonymous callers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liked_by_me: Option<bool>,
+    // whether a password protects the content
+    pub protected: bool,
+    // whether the content was withheld from the caller
+    pub locked: bool,
+    #[serde(flatten)]
+    pub outline: Option<Outline>,
+}
+
+impl ArticleDetail {
+    /// Withhold the content of a protected article, only its metadata is left
+    pub fn lock(&mut self) {
+        if self.protected {
+            self.article.content.clear();
+            self.article.content_html = None;
+            self.outline = None;
+            self.locked = true;
+        }
+    }
 }
 
 impl Article {
-    pub async fn create(pool: &MySqlPool, author_id: i32, data: &CreateArticle) -> AppResult<u64> {
-        let last_id = sqlx::query_as!(
-            PublicTag,
+    pub async fn create(
+        pool: &MySqlPool,
+        author_id: i32,
+        data: &CreateArticle,
+        published_at: Option<NaiveDateTime>,
+    ) -> AppResult<u64> {
+        let mut tx = pool.begin().await?;
+
+        let base = data.slug.as_deref().unwrap_or(&data.title);
+        let slug = Self::unique_slug(&mut tx, &slugify(base), None).await?;
+        let password_hash = data.password.as_deref().map(generate_hash).transpose()?;
+        let last_id = sqlx::query!(
             r#"
-                INSERT INTO article(title, slug, content, summary, cover, status, password, category_id, user_id)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);
+                INSERT INTO article(title, slug, content, content_html, summary, cover, status, published_at, unpublish_at, password_hash, category_id, user_id)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
             "#,
             data.title,
-            data.slug,
+            slug,
             data.content,
-            data.summary,
+            markdown::render(&data.content),
+            summary_or_excerpt(&data.summary, &data.content, password_hash.is_some()),
             data.cover,
             data.status,
-            data.password,
+            published_at,
+            data.unpublish_at,
+            password_hash,
             data.category_id,
             author_id,
         )
-        .execute(pool)
+        .execute(&mut tx)
         .await?
         .last_insert_id();
 
+        ArticleTag::replace(&mut tx, last_id as i32, &data.tag_ids).await?;
+        ArticleRevision::create(&mut tx, last_id as i32, author_id).await?;
+        tx.commit().await?;
+
         Ok(last_id)
     }
 
     pub async fn find_by_id(pool: &MySqlPool, id: i32) -> AppResult<Option<PublicArticle>> {
         let row = sqlx::query_as!(
             PublicArticle,
-            "SELECT id, title, slug, content, summary, cover, status, read_count, like_count, is_top, category_id, user_id, created_at, updated_at FROM article WHERE id = ?",
+            r#"
+                SELECT id, title, slug, content, content_html, summary, cover, status as `status: ArticleStatus`, read_count, like_count,
+                    is_top, published_at, unpublish_at, category_id, user_id,
+                    (SELECT count(*) FROM comment WHERE comment.article_id = article.id) as `comment_count!`,
+                    created_at, updated_at, password_hash
+                FROM article WHERE id = ? AND deleted_at IS NULL
+            "#,
             id
         )
         .fetch_optional(pool)
@@ -109,69 +470,291 @@ impl Article {
         Ok(row)
     }
 
+    pub async fn find_id_by_slug(pool: &MySqlPool, slug: &str) -> AppResult<Option<i32>> {
+        let row = sqlx::query!(
+            "SELECT id FROM article WHERE slug = ? AND deleted_at IS NULL",
+            slug
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| r.id))
+    }
+
+    /// The article as seen by `viewer`, with its tags and outline
+    pub async fn find_detail(
+        pool: &MySqlPool,
+        id: i32,
+        viewer: Option<i32>,
+    ) -> AppResult<Option<ArticleDetail>> {
+        let article = match Self::find_by_id(pool, id).await? {
+            Some(article) => article,
+            None => return Ok(None),
+        };
+
+        let detail = Self::with_tags(pool, vec![article], viewer)
+            .await?
+            .pop()
+            .map(|mut d| {
+                d.outline = Some(markdown::outline(&d.article.content));
+                d
+            });
+
+        Ok(detail)
+    }
+
     pub async fn find_list(
         pool: &MySqlPool,
+        filter: &ArticleFilter,
+        viewer: Option<i32>,
         pagination: &Pagination,
-    ) -> AppResult<PaginationResponse<PublicArticle>> {
+    ) -> AppResult<PaginationResponse<ArticleDetail>> {
         let page = (pagination.page.unwrap_or(1) - 1).max(0);
         let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
 
-        let rows = sqlx::query_as!(
-            PublicArticle,
+        let mut builder = QueryBuilder::new(
             r#"
-                SELECT id, title, slug, content, summary, cover, status, read_count, like_count, is_top, category_id, user_id, created_at, updated_at FROM article
-                ORDER BY created_at DESC LIMIT ? OFFSET ?;
+                SELECT a.id, a.title, a.slug, a.content, a.content_html, a.summary, a.cover, a.status, a.read_count, a.like_count,
+                    a.is_top, a.published_at, a.unpublish_at, a.category_id, a.user_id,
+                    (SELECT count(*) FROM comment WHERE comment.article_id = a.id) as comment_count,
+                    a.created_at, a.updated_at, a.password_hash
+                FROM article a
             "#,
-            page_size,
-            page * page_size,
-        )
-        .fetch_all(pool)
-        .await?;
-
-        let row = sqlx::query!(r#"SELECT count(*) as total FROM user;"#)
-            .fetch_one(pool)
+        );
+        filter.push_where(&mut builder);
+        filter.push_order_by(&mut builder);
+        builder.push(" LIMIT ");
+        builder.push_bind(page_size);
+        builder.push(" OFFSET ");
+        builder.push_bind(page * page_size);
+        let rows = builder
+            .build_query_as::<PublicArticle>()
+            .fetch_all(pool)
             .await?;
 
+        let mut builder = QueryBuilder::new("SELECT count(*) FROM article a");
+        filter.push_where(&mut builder);
+        let (total,): (i64,) = builder.build_query_as().fetch_one(pool).await?;
+
         let pagination = PaginationResponse {
             page: page + 1,
             page_size,
-            total: row.total as i32,
-            list: rows,
+            total: total as i32,
+            list: Self::with_tags(pool, rows, viewer).await?,
         };
 
         Ok(pagination)
     }
 
-    pub async fn update(pool: &MySqlPool, id: i32, data: &UpdateArticle) -> AppResult<bool> {
+    /// `slug`, or `slug-2`, `slug-3`... when it is taken by another article, now or formerly
+    async fn unique_slug(
+        tx: &mut Transaction<'_, MySql>,
+        slug: &str,
+        article_id: Option<i32>,
+    ) -> AppResult<String> {
+        let article_id = article_id.unwrap_or(0);
+        let mut candidate = slug.to_string();
+        for n in 2.. {
+            let row = sqlx::query!(
+                r#"
+                    SELECT
+                        (SELECT count(*) FROM article WHERE slug = ? AND id != ?)
+                        + (SELECT count(*) FROM slug_redirect WHERE slug = ? AND article_id != ?) as `taken!`
+                "#,
+                candidate,
+                article_id,
+                candidate,
+                article_id,
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if row.taken == 0 {
+                break;
+            }
+            candidate = format!("{}-{}", slug, n);
+        }
+
+        Ok(candidate)
+    }
+
+    /// Attach the tags and category paths of all the given articles and whether `viewer`
+    /// liked them, loaded in a single query each. Articles saved before html was cached get it rendered here.
+    async fn with_tags(
+        pool: &MySqlPool,
+        articles: Vec<PublicArticle>,
+        viewer: Option<i32>,
+    ) -> AppResult<Vec<ArticleDetail>> {
+        let ids: Vec<i32> = articles.iter().map(|a| a.id).collect();
+        let mut tags = ArticleTag::find_by_article_ids(pool, &ids).await?;
+        let mut category_ids: Vec<i32> = articles.iter().map(|a| a.category_id).collect();
+        category_ids.sort_unstable();
+        category_ids.dedup();
+        let paths = Category::find_paths(pool, &category_ids).await?;
+        let liked = match viewer {
+            Some(user_id) => {
+                Some(Like::find_liked(pool, LikeTarget::Article, user_id, &ids).await?)
+            }
+            None => None,
+        };
+
+        let list = articles
+            .into_iter()
+            .map(|mut article| {
+                if article.content_html.is_none() {
+                    article.content_html = Some(markdown::render(&article.content));
+                }
+                ArticleDetail {
+                    tags: tags.remove(&article.id).unwrap_or_default(),
+                    category_path: paths.get(&article.category_id).cloned().unwrap_or_default(),
+                    liked_by_me: liked.as_ref().map(|liked| liked.contains(&article.id)),
+                    protected: article.password_hash.is_some(),
+                    locked: false,
+                    article,
+                    outline: None,
+                }
+            })
+            .collect();
+
+        Ok(list)
+    }
+
+    /// Save the article by `editor_id`, recording a revision and keeping the latest
+    /// `retention` ones
+    pub async fn update(
+        pool: &MySqlPool,
+        id: i32,
+        editor_id: i32,
+        data: &UpdateArticle,
+        retention: u32,
+    ) -> AppResult<bool> {
+        let mut tx = pool.begin().await?;
+
+        let current = sqlx::query!(
+            "SELECT slug as `slug!`, password_hash FROM article WHERE id = ? AND deleted_at IS NULL FOR UPDATE",
+            id
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+        let (current, password_hash): (String, Option<String>) = match current {
+            Some(row) => (row.slug, row.password_hash),
+            None => return Ok(false),
+        };
+        let password_hash = match data.password.as_deref() {
+            Some("") => None,
+            Some(password) => Some(generate_hash(password)?),
+            None => password_hash,
+        };
+        let slug: String = match data.slug.as_deref().map(slugify) {
+            Some(slug) if slug != current => {
+                let slug = Self::unique_slug(&mut tx, &slug, Some(id)).await?;
+                SlugRedirect::delete(&mut tx, &slug).await?;
+                SlugRedirect::create(&mut tx, &current, id).await?;
+                slug
+            }
+            _ => current.clone(),
+        };
+        ArticleRevision::create_initial(&mut tx, id).await?;
+
         let effect_rows = sqlx::query!(
             r#"
                 UPDATE article SET
                     title = ?,
                     slug = ?,
                     content = ?,
+                    content_html = ?,
                     summary = ?,
                     cover = ?,
-                    status = ?,
-                    read_count = ?,
-                    like_count = ?,
                     is_top = ?,
-                    password = ?,
+                    password_hash = ?,
                     category_id = ?
-                WHERE id = ?
+                WHERE id = ? AND deleted_at IS NULL
             "#,
             data.title,
-            data.slug,
+            slug,
             data.content,
-            data.summary,
+            markdown::render(&data.content),
+            summary_or_excerpt(&data.summary, &data.content, password_hash.is_some()),
             data.cover,
-            data.status,
-            data.read_count,
-            data.like_count,
             data.is_top,
-            data.password,
+            password_hash,
             data.category_id,
             id,
         )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        if let Some(tag_ids) = &data.tag_ids {
+            ArticleTag::replace(&mut tx, id, tag_ids).await?;
+        }
+        ArticleRevision::create(&mut tx, id, editor_id).await?;
+        ArticleRevision::prune(&mut tx, id, retention).await?;
+        tx.commit().await?;
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Bring back the title, content and summary of a revision, as a new revision
+    pub async fn restore_revision(
+        pool: &MySqlPool,
+        id: i32,
+        editor_id: i32,
+        revision: &PublicRevision,
+        retention: u32,
+    ) -> AppResult<bool> {
+        let mut tx = pool.begin().await?;
+
+        let current = sqlx::query!(
+            "SELECT password_hash FROM article WHERE id = ? AND deleted_at IS NULL FOR UPDATE",
+            id
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+        let protected = match current {
+            Some(row) => row.password_hash.is_some(),
+            None => return Ok(false),
+        };
+
+        ArticleRevision::create_initial(&mut tx, id).await?;
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE article SET title = ?, content = ?, content_html = ?, summary = ?
+                WHERE id = ? AND deleted_at IS NULL
+            "#,
+            revision.title,
+            revision.content,
+            markdown::render(&revision.content),
+            restored_summary(&revision.summary, &revision.content, protected),
+            id,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        ArticleRevision::create(&mut tx, id, editor_id).await?;
+        ArticleRevision::prune(&mut tx, id, retention).await?;
+        tx.commit().await?;
+
+        Ok(effect_rows == 1)
+    }
+
+    pub async fn update_status(
+        pool: &MySqlPool,
+        id: i32,
+        status: ArticleStatus,
+        published_at: Option<NaiveDateTime>,
+        unpublish_at: Option<NaiveDateTime>,
+    ) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE article SET status = ?, published_at = ?, unpublish_at = ?
+                WHERE id = ? AND deleted_at IS NULL
+            "#,
+            status,
+            published_at,
+            unpublish_at,
+            id,
+        )
         .execute(pool)
         .await?
         .rows_affected();
@@ -179,16 +762,407 @@ impl Article {
         Ok(effect_rows == 1)
     }
 
-    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<()> {
-        sqlx::query!(
+    /// Publish at most `limit` scheduled articles whose `published_at` has been reached,
+    /// returning their ids
+    pub async fn publish_due(
+        pool: &MySqlPool,
+        now: NaiveDateTime,
+        limit: i64,
+    ) -> AppResult<Vec<i32>> {
+        let mut tx = pool.begin().await?;
+
+        // locked rows are being handled by another worker, skip them
+        let ids: Vec<i32> = sqlx::query!(
             r#"
-                delete from article where id = ?
+                SELECT id FROM article
+                WHERE status = ? AND published_at <= ? AND deleted_at IS NULL
+                ORDER BY published_at LIMIT ?
+                FOR UPDATE SKIP LOCKED
+            "#,
+            ArticleStatus::Scheduled,
+            now,
+            limit,
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|r| r.id)
+        .collect();
+
+        for id in &ids {
+            sqlx::query!(
+                r#"
+                    UPDATE article SET status = ? WHERE id = ?
+                "#,
+                ArticleStatus::Published,
+                id,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
+    /// Archive at most `limit` published articles whose `unpublish_at` has been reached,
+    /// returning their ids
+    pub async fn archive_expired(
+        pool: &MySqlPool,
+        now: NaiveDateTime,
+        limit: i64,
+    ) -> AppResult<Vec<i32>> {
+        let mut tx = pool.begin().await?;
+
+        // locked rows are being handled by another worker, skip them
+        let ids: Vec<i32> = sqlx::query!(
+            r#"
+                SELECT id FROM article
+                WHERE status = ? AND unpublish_at <= ? AND deleted_at IS NULL
+                ORDER BY unpublish_at LIMIT ?
+                FOR UPDATE SKIP LOCKED
+            "#,
+            ArticleStatus::Published,
+            now,
+            limit,
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|r| r.id)
+        .collect();
+
+        for id in &ids {
+            sqlx::query!(
+                r#"
+                    UPDATE article SET status = ?, unpublish_at = NULL WHERE id = ?
+                "#,
+                ArticleStatus::Archived,
+                id,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
+    /// Hash the passwords left in clear text by older versions, marked with a `plain:`
+    /// prefix. An empty password never protected anything, it is removed.
+    pub async fn hash_plain_passwords(pool: &MySqlPool) -> AppResult<u64> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT id, password_hash as `password_hash!` FROM article WHERE password_hash LIKE 'plain:%'
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut count = 0;
+        for row in rows {
+            let password = &row.password_hash["plain:".len()..];
+            let password_hash = if password.is_empty() {
+                None
+            } else {
+                Some(generate_hash(password)?)
+            };
+            // a password set meanwhile is not overwritten
+            count += sqlx::query!(
+                r#"
+                    UPDATE article SET password_hash = ?, updated_at = updated_at
+                    WHERE id = ? AND password_hash = ?
+                "#,
+                password_hash,
+                row.id,
+                row.password_hash,
+            )
+            .execute(pool)
+            .await?
+            .rows_affected();
+        }
+
+        Ok(count)
+    }
+
+    /// Move the article to the trash, its slug stays reserved until it is purged
+    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE article SET deleted_at = NOW() WHERE id = ? AND deleted_at IS NULL
             "#,
             id
         )
         .execute(pool)
         .await?
         .rows_affected();
-        Ok(())
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Articles in the trash, only the ones written by `user_id` when given
+    pub async fn find_trash(
+        pool: &MySqlPool,
+        user_id: Option<i32>,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<TrashedArticle>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let rows = sqlx::query_as!(
+            TrashedArticle,
+            r#"
+                SELECT id, title, slug, status as `status: ArticleStatus`, category_id, user_id,
+                    created_at, updated_at, deleted_at as `deleted_at!`
+                FROM article
+                WHERE deleted_at IS NOT NULL AND (? IS NULL OR user_id = ?)
+                ORDER BY deleted_at DESC LIMIT ? OFFSET ?;
+            "#,
+            user_id,
+            user_id,
+            page_size,
+            page * page_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let row = sqlx::query!(
+            r#"
+                SELECT count(*) as total FROM article
+                WHERE deleted_at IS NOT NULL AND (? IS NULL OR user_id = ?);
+            "#,
+            user_id,
+            user_id,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let pagination = PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: row.total as i32,
+            list: rows,
+        };
+
+        Ok(pagination)
+    }
+
+    /// Take the article out of the trash, only if written by `user_id` when given
+    pub async fn restore(pool: &MySqlPool, id: i32, user_id: Option<i32>) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE article SET deleted_at = NULL
+                WHERE id = ? AND deleted_at IS NOT NULL AND (? IS NULL OR user_id = ?)
+            "#,
+            id,
+            user_id,
+            user_id,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Delete for good at most `limit` articles trashed before `before`, along with
+    /// their comments, tags, revisions and former slugs
+    pub async fn purge(pool: &MySqlPool, before: NaiveDateTime, limit: i64) -> AppResult<u64> {
+        let effect_rows = sqlx::query!(
+            r#"
+                DELETE FROM article WHERE deleted_at < ? LIMIT ?
+            "#,
+            before,
+            limit,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows)
+    }
+}
+
+/// The summary given by the author, or the first paragraph of the content. The content
+/// of a protected article is not excerpted, it would be readable without the password.
+fn summary_or_excerpt(summary: &Option<String>, content: &str, protected: bool) -> Option<String> {
+    match summary {
+        Some(summary) if !summary.trim().is_empty() => Some(summary.clone()),
+        _ if protected => None,
+        _ => markdown::excerpt(content),
+    }
+}
+
+/// The summary of a restored revision. The revision may predate the password, a summary
+/// which is the excerpt of its content is then dropped, as protected articles get none.
+fn restored_summary(summary: &Option<String>, content: &str, protected: bool) -> Option<String> {
+    let summary = summary
+        .clone()
+        .filter(|summary| !protected || markdown::excerpt(content).as_ref() != Some(summary));
+    summary_or_excerpt(&summary, content, protected)
+}
+
+/// Url-friendly form of a title, chinese is transliterated to pinyin
+fn slugify(text: &str) -> String {
+    let slug = slug::slugify(text);
+    // leave room for a collision suffix in the 128 characters column
+    let slug = slug.get(..100).unwrap_or(&slug).trim_end_matches('-');
+    if slug.is_empty() {
+        String::from("article")
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The values a filter binds, in order
+    #[derive(Default)]
+    struct Binds(Vec<String>);
+
+    impl<'q> WhereClause<'q> for Binds {
+        fn push(&mut self, _sql: &str) -> &mut Self {
+            self
+        }
+
+        fn push_bind<T>(&mut self, value: T) -> &mut Self
+        where
+            T: 'q + Send + Encode<'q, MySql> + Type<MySql> + Debug,
+        {
+            self.0.push(format!("{:?}", value));
+            self
+        }
+    }
+
+    fn binds(filter: ArticleFilter) -> Vec<String> {
+        let mut binds = Binds::default();
+        filter.push_where(&mut binds);
+        binds.0
+    }
+
+    #[test]
+    fn tag_filters_bind_the_tag() {
+        let by_id = binds(ArticleFilter {
+            tag_id: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(by_id, ["Published", "3"]);
+
+        let by_name = binds(ArticleFilter {
+            tag: Some(String::from("rust")),
+            ..Default::default()
+        });
+        assert_eq!(by_name, ["Published", "\"rust\""]);
+    }
+
+    #[test]
+    fn category_filters_bind_the_category_once() {
+        for include_descendants in [None, Some(false), Some(true)] {
+            let filter = ArticleFilter {
+                category_id: Some(4),
+                include_descendants,
+                ..Default::default()
+            };
+            assert_eq!(binds(filter), ["Published", "4"]);
+        }
+
+        // without a category there is nothing to include
+        let filter = ArticleFilter {
+            include_descendants: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(binds(filter), ["Published"]);
+    }
+
+    #[test]
+    fn lists_show_what_the_caller_may_see() {
+        assert_eq!(binds(ArticleFilter::default()), ["Published"]);
+
+        let author = binds(ArticleFilter {
+            visibility: Visibility::Author(7),
+            ..Default::default()
+        });
+        assert_eq!(author, ["Published", "7"]);
+
+        let moderator = binds(ArticleFilter {
+            visibility: Visibility::All,
+            ..Default::default()
+        });
+        assert!(moderator.is_empty());
+    }
+
+    #[test]
+    fn status_transitions_follow_the_workflow() {
+        use ArticleStatus::*;
+
+        assert!(Draft.can_transition_to(Pending));
+        assert!(Pending.can_transition_to(Published));
+        assert!(Scheduled.can_transition_to(Scheduled));
+        assert!(Published.can_transition_to(Archived));
+        assert!(Archived.can_transition_to(Published));
+
+        assert!(!Draft.can_transition_to(Archived));
+        assert!(!Pending.can_transition_to(Archived));
+        assert!(!Published.can_transition_to(Scheduled));
+        assert!(!Archived.can_transition_to(Pending));
+    }
+
+    #[test]
+    fn statuses_stay_as_they_are_only_when_scheduled() {
+        use ArticleStatus::*;
+
+        for status in [Draft, Pending, Published, Archived] {
+            assert!(!status.can_transition_to(status));
+        }
+    }
+
+    #[test]
+    fn only_moderators_take_articles_out_of_review() {
+        use ArticleStatus::*;
+
+        // sending a pending article back to draft and publishing it skips the review
+        let detour = [Pending, Draft, Published];
+        assert!(!detour.windows(2).all(|w| w[0].can_be_moved_to(w[1], false)));
+        assert!(!Pending.can_be_moved_to(Draft, false));
+        assert!(Draft.can_be_moved_to(Published, false));
+        assert!(!Pending.can_be_moved_to(Published, false));
+        assert!(!Pending.can_be_moved_to(Scheduled, false));
+
+        assert!(Pending.can_be_moved_to(Draft, true));
+        assert!(Pending.can_be_moved_to(Published, true));
+        assert!(Draft.can_be_moved_to(Pending, false));
+    }
+
+    #[test]
+    fn restored_summaries_hide_protected_content() {
+        let content = "First paragraph.\n\nSecond one.";
+        let excerpt = Some(String::from("First paragraph."));
+        let written = Some(String::from("Written by the author"));
+
+        assert_eq!(restored_summary(&excerpt, content, true), None);
+        assert_eq!(restored_summary(&None, content, true), None);
+        assert_eq!(restored_summary(&written, content, true), written);
+        assert_eq!(restored_summary(&excerpt, content, false), excerpt);
+        assert_eq!(restored_summary(&None, content, false), excerpt);
+    }
+
+    #[test]
+    fn slugs_are_cut_to_100_characters() {
+        let slug = slugify(&"word ".repeat(40));
+        assert!(slug.len() <= 100);
+        assert!(slug.starts_with("word-word"));
+        assert!(!slug.ends_with('-'));
+
+        let slug = slugify(&"文章标题".repeat(40));
+        assert!(slug.len() <= 100);
+        assert!(!slug.ends_with('-'));
+    }
+
+    #[test]
+    fn slugs_drop_punctuation() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("?!"), "article");
     }
 }
@@ -1,8 +1,111 @@
-use sqlx::FromRow;
+use std::collections::HashMap;
+
+use sqlx::{FromRow, MySql, MySqlPool, QueryBuilder, Transaction};
+
+use crate::{
+    errors::{AppResult, Error},
+    models::tag::TagSummary,
+};
 
 #[derive(FromRow)]
-pub struct Reply {
-    pub id: i32,
+pub struct ArticleTag {
     pub article_id: i32,
     pub tag_id: i32,
+    pub tag_name: String,
+}
+
+impl ArticleTag {
+    /// Replace the tags of an article with `tag_ids`, inside the caller's transaction
+    pub async fn replace(
+        tx: &mut Transaction<'_, MySql>,
+        article_id: i32,
+        tag_ids: &[i32],
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                delete from article_tag where article_id = ?
+            "#,
+            article_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut tag_ids = tag_ids.to_vec();
+        tag_ids.sort_unstable();
+        tag_ids.dedup();
+        if tag_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder =
+            QueryBuilder::new("SELECT count(*) FROM tag WHERE deleted_at IS NULL AND id IN (");
+        let mut ids = builder.separated(", ");
+        for id in &tag_ids {
+            ids.push_bind(id);
+        }
+        builder.push(")");
+        let (found,): (i64,) = builder.build_query_as().fetch_one(&mut *tx).await?;
+        if found != tag_ids.len() as i64 {
+            return Err(Error::BadRequest(String::from("unknown tag in tag_ids")));
+        }
+
+        let mut builder = QueryBuilder::new("INSERT INTO article_tag(article_id, tag_id) ");
+        builder.push_values(&tag_ids, |mut row, tag_id| {
+            row.push_bind(article_id).push_bind(tag_id);
+        });
+        builder.build().execute(&mut *tx).await?;
+
+        Ok(())
+    }
+
+    /// Articles carrying the tag, in the trash or not
+    pub async fn find_article_ids(pool: &MySqlPool, tag_id: i32) -> AppResult<Vec<i32>> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT article_id FROM article_tag WHERE tag_id = ?
+            "#,
+            tag_id,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.article_id).collect())
+    }
+
+    /// Tags of each of the given articles, in a single query
+    pub async fn find_by_article_ids(
+        pool: &MySqlPool,
+        article_ids: &[i32],
+    ) -> AppResult<HashMap<i32, Vec<TagSummary>>> {
+        let mut tags: HashMap<i32, Vec<TagSummary>> = HashMap::new();
+        if article_ids.is_empty() {
+            return Ok(tags);
+        }
+
+        let mut builder = QueryBuilder::new(
+            r#"
+                SELECT at.article_id, at.tag_id, t.name as tag_name
+                FROM article_tag at INNER JOIN tag t ON t.id = at.tag_id
+                WHERE t.deleted_at IS NULL AND at.article_id IN (
+            "#,
+        );
+        let mut ids = builder.separated(", ");
+        for id in article_ids {
+            ids.push_bind(id);
+        }
+        builder.push(") ORDER BY t.name");
+
+        let rows = builder
+            .build_query_as::<ArticleTag>()
+            .fetch_all(pool)
+            .await?;
+        for row in rows {
+            tags.entry(row.article_id).or_default().push(TagSummary {
+                id: row.tag_id,
+                name: row.tag_name,
+            });
+        }
+
+        Ok(tags)
+    }
 }
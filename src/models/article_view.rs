@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlPool};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::errors::AppResult;
+
+/// Views of an article on one day
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
+pub struct DailyViews {
+    pub date: NaiveDate,
+    pub views: i32,
+}
+
+/// Query parameters of the daily views of an article, both ends inclusive
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ViewRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+pub struct ArticleView;
+
+impl ArticleView {
+    /// Add views counted per article and day to the daily counts and to `read_count`,
+    /// in a single transaction. Views of articles deleted meanwhile are dropped.
+    pub async fn add(pool: &MySqlPool, views: &HashMap<(i32, NaiveDate), i32>) -> AppResult<()> {
+        let mut totals: HashMap<i32, i32> = HashMap::new();
+        for ((article_id, _), count) in views {
+            *totals.entry(*article_id).or_default() += count;
+        }
+
+        let mut tx = pool.begin().await?;
+        for ((article_id, date), count) in views {
+            sqlx::query!(
+                r#"
+                    INSERT INTO article_view(article_id, date, views)
+                    SELECT id, ?, ? FROM article WHERE id = ?
+                    ON DUPLICATE KEY UPDATE views = views + VALUES(views)
+                "#,
+                date,
+                count,
+                article_id,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        for (article_id, count) in totals {
+            // a view is no edit, `updated_at` is left as it is
+            sqlx::query!(
+                r#"
+                    UPDATE article SET read_count = read_count + ?, updated_at = updated_at WHERE id = ?
+                "#,
+                count,
+                article_id,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Views of the article per day from `from` to `to`, days without views are left out
+    pub async fn find_daily(
+        pool: &MySqlPool,
+        article_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<DailyViews>> {
+        let rows = sqlx::query_as!(
+            DailyViews,
+            r#"
+                SELECT date, views FROM article_view
+                WHERE article_id = ? AND date BETWEEN ? AND ?
+                ORDER BY date
+            "#,
+            article_id,
+            from,
+            to,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+}
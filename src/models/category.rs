@@ -1,10 +1,15 @@
+use std::collections::{HashMap, HashSet};
+
 use chrono::NaiveDateTime;
-use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, MySqlPool};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{FromRow, MySql, MySqlPool, QueryBuilder, Transaction};
+use utoipa::ToSchema;
+use validator::Validate;
 
 use crate::{
     api::{Pagination, PaginationResponse},
-    errors::AppResult,
+    errors::{AppResult, Error},
+    models::article::ArticleStatus,
 };
 
 #[derive(FromRow)]
@@ -12,48 +17,104 @@ pub struct Category {
     pub id: i32,
     pub name: String,
     pub description: String,
+    pub parent_id: Option<i32>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
     pub deleted_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct CategoryData {
+    #[validate(length(min = 1, max = 64, message = "must be 1-64 characters"))]
     pub name: String,
+    #[validate(length(max = 128, message = "must be at most 128 characters"))]
     pub description: Option<String>,
+    // `null` makes a top-level category, absent keeps the current parent on updates and a
+    // new parent moves the subcategories along
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<i32>)]
+    pub parent_id: Option<Option<i32>>,
+}
+
+/// Tell a `null` field, `Some(None)`, from an absent one, `None`
+fn present<'de, D>(deserializer: D) -> Result<Option<Option<i32>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
 }
 
-#[derive(FromRow, Debug, Clone, Serialize)]
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
 pub struct PublicCategory {
     pub id: i32,
     pub name: String,
     pub description: Option<String>,
+    pub parent_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A category in the trash
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
+pub struct TrashedCategory {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub parent_id: Option<i32>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub deleted_at: NaiveDateTime,
+}
+
+/// The category fields embedded in article responses
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CategorySummary {
+    pub id: i32,
+    pub name: String,
+}
+
+/// A category with its subcategories. `article_count` counts the published articles right
+/// in it, `total_article_count` those of its subcategories too.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CategoryNode {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub article_count: i64,
+    pub total_article_count: i64,
+    pub children: Vec<CategoryNode>,
 }
 
 impl Category {
     pub async fn create(pool: &MySqlPool, data: &CategoryData) -> AppResult<u64> {
+        let mut tx = pool.begin().await?;
+        let parent_id = data.parent_id.flatten();
+        if let Some(parent_id) = parent_id {
+            Self::check_parent(&mut tx, None, parent_id).await?;
+        }
+
         let last_id = sqlx::query_as!(
             PublicCategory,
             r#"
-                INSERT INTO category(name, description)
-                VALUES (?, ?);
+                INSERT INTO category(name, description, parent_id)
+                VALUES (?, ?, ?);
             "#,
             data.name,
             data.description,
+            parent_id,
         )
-        .execute(pool)
+        .execute(&mut *tx)
         .await?
         .last_insert_id();
 
+        tx.commit().await?;
         Ok(last_id)
     }
 
     pub async fn find_by_id(pool: &MySqlPool, id: i32) -> AppResult<Option<PublicCategory>> {
         let row = sqlx::query_as!(
             PublicCategory,
-            "SELECT id, name, description, created_at, updated_at FROM category WHERE id = ?",
+            "SELECT id, name, description, parent_id, created_at, updated_at FROM category WHERE id = ? AND deleted_at IS NULL",
             id
         )
         .fetch_optional(pool)
@@ -65,7 +126,7 @@ impl Category {
     pub async fn find_by_name(pool: &MySqlPool, name: &str) -> AppResult<Option<PublicCategory>> {
         let row = sqlx::query_as!(
             PublicCategory,
-            "SELECT id, name, description, created_at, updated_at FROM category WHERE name = ?",
+            "SELECT id, name, description, parent_id, created_at, updated_at FROM category WHERE name = ? AND deleted_at IS NULL",
             name
         )
         .fetch_optional(pool)
@@ -84,7 +145,8 @@ impl Category {
         let rows = sqlx::query_as!(
             PublicCategory,
             r#"
-                SELECT id, name, description, created_at, updated_at FROM category
+                SELECT id, name, description, parent_id, created_at, updated_at FROM category
+                WHERE deleted_at IS NULL
                 ORDER BY created_at DESC LIMIT ? OFFSET ?;
             "#,
             page_size,
@@ -93,9 +155,10 @@ impl Category {
         .fetch_all(pool)
         .await?;
 
-        let row = sqlx::query!(r#"SELECT count(*) as total FROM user;"#)
-            .fetch_one(pool)
-            .await?;
+        let row =
+            sqlx::query!(r#"SELECT count(*) as total FROM category WHERE deleted_at IS NULL;"#)
+                .fetch_one(pool)
+                .await?;
 
         let pagination = PaginationResponse {
             page: page + 1,
@@ -107,35 +170,365 @@ impl Category {
         Ok(pagination)
     }
 
+    /// Save the category, a new parent moves it together with its subcategories
     pub async fn update(pool: &MySqlPool, id: i32, data: &CategoryData) -> AppResult<bool> {
+        let mut tx = pool.begin().await?;
+        if let Some(Some(parent_id)) = data.parent_id {
+            Self::check_parent(&mut tx, Some(id), parent_id).await?;
+        }
+
         let effect_rows = sqlx::query!(
             r#"
                 UPDATE category SET
                     name = ?,
-                    description = ?
-                WHERE id = ?
+                    description = ?,
+                    parent_id = IF(?, ?, parent_id)
+                WHERE id = ? AND deleted_at IS NULL
             "#,
             data.name,
             data.description,
+            data.parent_id.is_some(),
+            data.parent_id.flatten(),
             id,
         )
-        .execute(pool)
+        .execute(&mut *tx)
         .await?
         .rows_affected();
 
+        tx.commit().await?;
         Ok(effect_rows == 1)
     }
 
-    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<()> {
+    /// Check that `parent_id` may become the parent of category `id`, or of a new one when
+    /// `None`: it has to exist, and must be neither the category itself nor one of its
+    /// subcategories. The category and the ancestors of the new parent are locked one by
+    /// one while walking up, so concurrent moves can not form a cycle either, while writes
+    /// to other branches go on.
+    async fn check_parent(
+        tx: &mut Transaction<'_, MySql>,
+        id: Option<i32>,
+        parent_id: i32,
+    ) -> AppResult<()> {
+        if let Some(id) = id {
+            sqlx::query!("SELECT id FROM category WHERE id = ? FOR UPDATE", id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        }
+
+        let mut visited = HashSet::new();
+        let mut ancestor = Some(parent_id);
+        while let Some(current) = ancestor {
+            if Some(current) == id {
+                return Err(Error::BadRequest(String::from(
+                    "a category can not be moved under itself or its subcategories",
+                )));
+            }
+            // stop in case the table already holds a cycle
+            if !visited.insert(current) {
+                break;
+            }
+
+            let row = sqlx::query!(
+                "SELECT parent_id, deleted_at FROM category WHERE id = ? FOR UPDATE",
+                current
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            let live = row.as_ref().is_some_and(|r| r.deleted_at.is_none());
+            if current == parent_id && !live {
+                return Err(Error::BadRequest(String::from("unknown parent category")));
+            }
+            ancestor = row.and_then(|r| r.parent_id);
+        }
+
+        Ok(())
+    }
+
+    /// Whether categories outside the trash still have `id` as parent
+    async fn has_children(tx: &mut Transaction<'_, MySql>, id: i32) -> AppResult<bool> {
+        let row = sqlx::query!(
+            r#"
+                SELECT count(*) as total FROM category WHERE parent_id = ? AND deleted_at IS NULL;
+            "#,
+            id,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(row.total > 0)
+    }
+
+    /// Whether the parent of category `id` is in the trash
+    pub async fn parent_in_trash(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let row = sqlx::query!(
+            r#"
+                SELECT count(*) as total FROM category c
+                INNER JOIN category p ON p.id = c.parent_id
+                WHERE c.id = ? AND p.deleted_at IS NOT NULL;
+            "#,
+            id,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.total > 0)
+    }
+
+    /// Every category outside the trash, nested under its parent and sorted by name
+    pub async fn find_tree(pool: &MySqlPool) -> AppResult<Vec<CategoryNode>> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT id, name, description, parent_id,
+                    (SELECT count(*) FROM article
+                        WHERE article.category_id = category.id AND article.status = ? AND article.deleted_at IS NULL) as `article_count!`
+                FROM category WHERE deleted_at IS NULL
+                ORDER BY name
+            "#,
+            ArticleStatus::Published,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut children: HashMap<Option<i32>, Vec<CategoryNode>> = HashMap::new();
+        for row in rows {
+            children
+                .entry(row.parent_id)
+                .or_default()
+                .push(CategoryNode {
+                    id: row.id,
+                    name: row.name,
+                    description: row.description,
+                    article_count: row.article_count,
+                    total_article_count: row.article_count,
+                    children: vec![],
+                });
+        }
+
+        Ok(Self::nest(&mut children, None))
+    }
+
+    /// Take the categories under `parent` out of `children`, each with its own subtree
+    fn nest(
+        children: &mut HashMap<Option<i32>, Vec<CategoryNode>>,
+        parent: Option<i32>,
+    ) -> Vec<CategoryNode> {
+        let mut nodes = children.remove(&parent).unwrap_or_default();
+        for node in &mut nodes {
+            node.children = Self::nest(children, Some(node.id));
+            node.total_article_count += node
+                .children
+                .iter()
+                .map(|c| c.total_article_count)
+                .sum::<i64>();
+        }
+        nodes
+    }
+
+    /// The path from the top-level category down to each of the given categories, in a
+    /// single query
+    pub async fn find_paths(
+        pool: &MySqlPool,
+        ids: &[i32],
+    ) -> AppResult<HashMap<i32, Vec<CategorySummary>>> {
+        let mut paths: HashMap<i32, Vec<CategorySummary>> = HashMap::new();
+        if ids.is_empty() {
+            return Ok(paths);
+        }
+
+        let mut builder = QueryBuilder::new(
+            r#"
+                WITH RECURSIVE path AS (
+                    SELECT id as category_id, id, name, parent_id, 0 as depth
+                    FROM category WHERE id IN (
+            "#,
+        );
+        let mut separated = builder.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        builder.push(
+            r#"
+                    )
+                    UNION ALL
+                    SELECT path.category_id, c.id, c.name, c.parent_id, path.depth + 1
+                    FROM category c INNER JOIN path ON c.id = path.parent_id
+                )
+                SELECT category_id, id, name FROM path ORDER BY category_id, depth DESC
+            "#,
+        );
+        let rows: Vec<(i32, i32, String)> = builder.build_query_as().fetch_all(pool).await?;
+
+        for (category_id, id, name) in rows {
+            paths
+                .entry(category_id)
+                .or_default()
+                .push(CategorySummary { id, name });
+        }
+
+        Ok(paths)
+    }
+
+    /// Move the category to the trash, unless subcategories outside the trash are left
+    /// under it. The row stays locked meanwhile, so a concurrent move can not put one
+    /// under it: `check_parent` locks the new parent too.
+    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let mut tx = pool.begin().await?;
+        let row = sqlx::query!(
+            "SELECT id FROM category WHERE id = ? AND deleted_at IS NULL FOR UPDATE",
+            id
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+        if row.is_none() {
+            return Ok(false);
+        }
+        if Self::has_children(&mut tx, id).await? {
+            return Err(Error::BadRequest(String::from(
+                "category has subcategories, move or delete them first",
+            )));
+        }
+
         sqlx::query!(
             r#"
-                delete from category where id = ?
+                UPDATE category SET deleted_at = NOW() WHERE id = ?
+            "#,
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Whether a category in the trash still holds the name
+    pub async fn exists_in_trash(pool: &MySqlPool, name: &str) -> AppResult<bool> {
+        let row = sqlx::query!(
+            r#"
+                SELECT count(*) as total FROM category WHERE name = ? AND deleted_at IS NOT NULL;
+            "#,
+            name,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.total > 0)
+    }
+
+    pub async fn find_trash(
+        pool: &MySqlPool,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<TrashedCategory>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let rows = sqlx::query_as!(
+            TrashedCategory,
+            r#"
+                SELECT id, name, description, parent_id, created_at, updated_at, deleted_at as `deleted_at!` FROM category
+                WHERE deleted_at IS NOT NULL
+                ORDER BY deleted_at DESC LIMIT ? OFFSET ?;
+            "#,
+            page_size,
+            page * page_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let row =
+            sqlx::query!(r#"SELECT count(*) as total FROM category WHERE deleted_at IS NOT NULL;"#)
+                .fetch_one(pool)
+                .await?;
+
+        let pagination = PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: row.total as i32,
+            list: rows,
+        };
+
+        Ok(pagination)
+    }
+
+    /// Take the category out of the trash
+    pub async fn restore(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE category SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL
             "#,
             id
         )
         .execute(pool)
         .await?
         .rows_affected();
-        Ok(())
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Delete for good at most `limit` categories trashed before `before`. Categories are
+    /// kept while articles or subcategories still belong to them.
+    pub async fn purge(pool: &MySqlPool, before: NaiveDateTime, limit: i64) -> AppResult<u64> {
+        // the parents are read through a derived table, MySQL does not let a DELETE read
+        // its own table otherwise
+        let effect_rows = sqlx::query!(
+            r#"
+                DELETE FROM category
+                WHERE deleted_at < ? AND NOT EXISTS (SELECT 1 FROM article WHERE article.category_id = category.id)
+                    AND id NOT IN (SELECT parent_id FROM (SELECT DISTINCT parent_id FROM category WHERE parent_id IS NOT NULL) as parents)
+                LIMIT ?
+            "#,
+            before,
+            limit,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i32, article_count: i64) -> CategoryNode {
+        CategoryNode {
+            id,
+            name: format!("category {}", id),
+            description: None,
+            article_count,
+            total_article_count: article_count,
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn totals_include_all_descendants() {
+        let mut children = HashMap::new();
+        children.insert(None, vec![node(1, 1), node(2, 0)]);
+        children.insert(Some(1), vec![node(3, 2)]);
+        children.insert(Some(3), vec![node(4, 4)]);
+
+        let tree = Category::nest(&mut children, None);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].total_article_count, 7);
+        assert_eq!(tree[0].article_count, 1);
+        assert_eq!(tree[0].children[0].total_article_count, 6);
+        assert_eq!(tree[0].children[0].children[0].id, 4);
+        assert_eq!(tree[1].total_article_count, 0);
+        assert!(children.is_empty());
+    }
+
+    #[test]
+    fn absent_parents_differ_from_null_ones() {
+        let parse = |json: &str| {
+            serde_json::from_str::<CategoryData>(json)
+                .unwrap()
+                .parent_id
+        };
+        assert_eq!(parse(r#"{"name": "a"}"#), None);
+        assert_eq!(parse(r#"{"name": "a", "parent_id": null}"#), Some(None));
+        assert_eq!(parse(r#"{"name": "a", "parent_id": 3}"#), Some(Some(3)));
     }
 }
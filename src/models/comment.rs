@@ -1,13 +1,219 @@
 use chrono::NaiveDateTime;
-use sqlx::FromRow;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlPool};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    api::{Pagination, PaginationResponse},
+    errors::AppResult,
+    models::reply::{PublicReply, Reply},
+};
+
+// replies embedded in each comment of a comment list, the rest is paged per thread
+const REPLY_PREVIEW_SIZE: i64 = 3;
 
 #[derive(FromRow)]
 pub struct Comment {
     pub id: i32,
     pub content: String,
-    pub article_id: String,
+    pub article_id: i32,
     pub user_id: i32,
     pub like_count: i32,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
 }
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CommentData {
+    #[validate(length(min = 1, max = 2000, message = "must be 1-2000 characters"))]
+    pub content: String,
+}
+
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
+pub struct PublicComment {
+    pub id: i32,
+    pub content: String,
+    pub article_id: i32,
+    pub user_id: i32,
+    pub user_name: String,
+    pub user_avatar: Option<String>,
+    pub like_count: i32,
+    // whether the caller liked it, absent for anonymous callers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liked_by_me: Option<bool>,
+    pub reply_count: i64,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A comment with the first replies of its thread
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: PublicComment,
+    pub replies: Vec<PublicReply>,
+}
+
+impl Comment {
+    pub async fn create(
+        pool: &MySqlPool,
+        article_id: i32,
+        user_id: i32,
+        data: &CommentData,
+    ) -> AppResult<u64> {
+        let last_id = sqlx::query!(
+            r#"
+                INSERT INTO comment(content, article_id, user_id)
+                VALUES (?, ?, ?);
+            "#,
+            data.content,
+            article_id,
+            user_id,
+        )
+        .execute(pool)
+        .await?
+        .last_insert_id();
+
+        Ok(last_id)
+    }
+
+    /// The comment as seen by `viewer`
+    pub async fn find_by_id(
+        pool: &MySqlPool,
+        id: i32,
+        viewer: Option<i32>,
+    ) -> AppResult<Option<PublicComment>> {
+        let row = sqlx::query_as!(
+            PublicComment,
+            r#"
+                SELECT c.id, c.content, c.article_id, c.user_id, u.name as user_name, u.avatar as user_avatar,
+                    c.like_count,
+                    IF(? IS NULL, NULL, EXISTS(SELECT 1 FROM comment_like l WHERE l.comment_id = c.id AND l.user_id = ?)) as `liked_by_me: bool`,
+                    (SELECT count(*) FROM reply r WHERE r.comment_id = c.id) as `reply_count!`,
+                    c.created_at, c.updated_at
+                FROM comment c INNER JOIN user u ON u.id = c.user_id
+                WHERE c.id = ?
+            "#,
+            viewer,
+            viewer,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn find_list(
+        pool: &MySqlPool,
+        article_id: i32,
+        viewer: Option<i32>,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<CommentThread>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let rows = sqlx::query_as!(
+            PublicComment,
+            r#"
+                SELECT c.id, c.content, c.article_id, c.user_id, u.name as user_name, u.avatar as user_avatar,
+                    c.like_count,
+                    IF(? IS NULL, NULL, EXISTS(SELECT 1 FROM comment_like l WHERE l.comment_id = c.id AND l.user_id = ?)) as `liked_by_me: bool`,
+                    (SELECT count(*) FROM reply r WHERE r.comment_id = c.id) as `reply_count!`,
+                    c.created_at, c.updated_at
+                FROM comment c INNER JOIN user u ON u.id = c.user_id
+                WHERE c.article_id = ?
+                ORDER BY c.created_at DESC LIMIT ? OFFSET ?;
+            "#,
+            viewer,
+            viewer,
+            article_id,
+            page_size,
+            page * page_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let row = sqlx::query!(
+            r#"SELECT count(*) as total FROM comment WHERE article_id = ?;"#,
+            article_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let ids: Vec<i32> = rows.iter().map(|c| c.id).collect();
+        let mut replies = Reply::find_preview(pool, &ids, REPLY_PREVIEW_SIZE, viewer).await?;
+        let list = rows
+            .into_iter()
+            .map(|comment| {
+                let (thread, rest) = replies
+                    .drain(..)
+                    .partition(|r| r.comment_id == Some(comment.id));
+                replies = rest;
+                CommentThread {
+                    comment,
+                    replies: thread,
+                }
+            })
+            .collect();
+
+        let pagination = PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: row.total as i32,
+            list,
+        };
+
+        Ok(pagination)
+    }
+
+    pub async fn update(pool: &MySqlPool, id: i32, data: &CommentData) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE comment SET content = ? WHERE id = ?
+            "#,
+            data.content,
+            id,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows == 1)
+    }
+
+    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                delete from comment where id = ?
+            "#,
+            id
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(content: String) -> CommentData {
+        CommentData { content }
+    }
+
+    #[test]
+    fn comments_need_content() {
+        assert!(comment(String::new()).validate().is_err());
+        assert!(comment(String::from("nice post")).validate().is_ok());
+    }
+
+    #[test]
+    fn comments_are_limited_to_2000_characters() {
+        assert!(comment("a".repeat(2000)).validate().is_ok());
+        assert!(comment("a".repeat(2001)).validate().is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use sqlx::{MySql, MySqlPool, QueryBuilder, Transaction};
+
+use crate::errors::AppResult;
+
+/// What can be liked, each kind has its own table of likes
+#[derive(Debug, Clone, Copy)]
+pub enum LikeTarget {
+    Article,
+    Comment,
+    Reply,
+}
+
+impl LikeTarget {
+    /// The liked table, the table of its likes and the column of the latter pointing
+    /// to the former
+    fn tables(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            LikeTarget::Article => ("article", "article_like", "article_id"),
+            LikeTarget::Comment => ("comment", "comment_like", "comment_id"),
+            LikeTarget::Reply => ("reply", "reply_like", "reply_id"),
+        }
+    }
+}
+
+pub struct Like;
+
+/// The like count once `delta` is applied, it never goes below 0 like the stored one
+fn counted(like_count: i32, delta: i32) -> i32 {
+    (like_count + delta).max(0)
+}
+
+impl Like {
+    /// Like `id` on behalf of `user_id`, returning its like count, or `None` when it does
+    /// not exist. Liking twice counts once.
+    pub async fn add(
+        pool: &MySqlPool,
+        target: LikeTarget,
+        id: i32,
+        user_id: i32,
+    ) -> AppResult<Option<i32>> {
+        Self::set(pool, target, id, user_id, true).await
+    }
+
+    /// Take back the like of `user_id`, returning the like count of `id`, or `None` when
+    /// it does not exist
+    pub async fn remove(
+        pool: &MySqlPool,
+        target: LikeTarget,
+        id: i32,
+        user_id: i32,
+    ) -> AppResult<Option<i32>> {
+        Self::set(pool, target, id, user_id, false).await
+    }
+
+    async fn set(
+        pool: &MySqlPool,
+        target: LikeTarget,
+        id: i32,
+        user_id: i32,
+        liked: bool,
+    ) -> AppResult<Option<i32>> {
+        let (table, like_table, column) = target.tables();
+        let mut tx = pool.begin().await?;
+
+        // the row lock keeps the counter in step with concurrent likes of the same item
+        let row: Option<(i32,)> = sqlx::query_as(&format!(
+            "SELECT like_count FROM {} WHERE id = ? FOR UPDATE",
+            table
+        ))
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?;
+        let like_count = match row {
+            Some((like_count,)) => like_count,
+            None => return Ok(None),
+        };
+
+        let sql = if liked {
+            format!(
+                "INSERT IGNORE INTO {}({}, user_id) VALUES (?, ?)",
+                like_table, column
+            )
+        } else {
+            format!(
+                "DELETE FROM {} WHERE {} = ? AND user_id = ?",
+                like_table, column
+            )
+        };
+        let changed = sqlx::query(&sql)
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        if changed == 0 {
+            return Ok(Some(like_count));
+        }
+
+        let delta = if liked { 1 } else { -1 };
+        // a like is no edit, `updated_at` is left as it is
+        sqlx::query(&format!(
+            "UPDATE {} SET like_count = GREATEST(like_count + ?, 0), updated_at = updated_at WHERE id = ?",
+            table
+        ))
+        .bind(delta)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(counted(like_count, delta)))
+    }
+
+    /// Take back every like of `user_id`, keeping the like counts in step, e.g. before the
+    /// user is deleted for good
+    pub async fn remove_by_user(tx: &mut Transaction<'_, MySql>, user_id: i32) -> AppResult<()> {
+        for target in [LikeTarget::Article, LikeTarget::Comment, LikeTarget::Reply] {
+            let (table, like_table, column) = target.tables();
+            // a user likes an item once, so each item loses one like
+            sqlx::query(&format!(
+                "UPDATE {} t INNER JOIN {} l ON l.{} = t.id SET t.like_count = GREATEST(t.like_count - 1, 0), t.updated_at = t.updated_at WHERE l.user_id = ?",
+                table, like_table, column
+            ))
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(&format!("DELETE FROM {} WHERE user_id = ?", like_table))
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Which of `ids` are liked by `user_id`, in a single query
+    pub async fn find_liked(
+        pool: &MySqlPool,
+        target: LikeTarget,
+        user_id: i32,
+        ids: &[i32],
+    ) -> AppResult<HashSet<i32>> {
+        if ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let (_, like_table, column) = target.tables();
+        let mut builder = QueryBuilder::new(format!(
+            "SELECT {} FROM {} WHERE user_id = ",
+            column, like_table
+        ));
+        builder.push_bind(user_id);
+        builder.push(format!(" AND {} IN (", column));
+        let mut separated = builder.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        builder.push(")");
+
+        let rows: Vec<(i32,)> = builder.build_query_as().fetch_all(pool).await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn likes_add_and_take_back_one() {
+        assert_eq!(counted(0, 1), 1);
+        assert_eq!(counted(5, 1), 6);
+        assert_eq!(counted(5, -1), 4);
+        assert_eq!(counted(1, -1), 0);
+    }
+
+    #[test]
+    fn like_counts_stop_at_zero() {
+        // e.g. a like taken back after the counter was reset
+        assert_eq!(counted(0, -1), 0);
+    }
+}
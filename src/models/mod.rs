@@ -1,8 +1,15 @@
 pub mod article;
-mod article_tag;
+pub mod article_tag;
+pub mod article_view;
 pub mod category;
-mod comment;
-mod reply;
-mod role;
+pub mod comment;
+pub mod like;
+pub mod reply;
+pub mod revision;
+pub mod role;
+pub mod search;
+pub mod sitemap;
+pub mod slug_redirect;
 pub mod tag;
+pub mod token;
 pub mod user;
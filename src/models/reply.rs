@@ -1,15 +1,267 @@
 use chrono::NaiveDateTime;
-use sqlx::FromRow;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlPool, QueryBuilder};
+use utoipa::ToSchema;
+use validator::Validate;
 
+use crate::{
+    api::{Pagination, PaginationResponse},
+    errors::AppResult,
+};
+
+/// A reply always belongs to the thread of `comment_id`. When it answers another
+/// reply, `reply_id` points to that reply and `reply_type` is set.
 #[derive(FromRow)]
 pub struct Reply {
     pub id: i32,
     pub content: String,
     pub user_id: i32,
-    pub comment_id: String,
-    pub reply_id: String,
+    pub comment_id: Option<i32>,
+    pub reply_id: Option<i32>,
     pub reply_type: bool,
     pub like_count: i32,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
 }
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateReply {
+    #[validate(length(min = 1, max = 2000, message = "must be 1-2000 characters"))]
+    pub content: String,
+    // the reply being answered, none when answering the comment itself
+    pub reply_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateReply {
+    #[validate(length(min = 1, max = 2000, message = "must be 1-2000 characters"))]
+    pub content: String,
+}
+
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
+pub struct PublicReply {
+    pub id: i32,
+    pub content: String,
+    pub user_id: i32,
+    pub user_name: String,
+    pub user_avatar: Option<String>,
+    pub comment_id: Option<i32>,
+    pub reply_id: Option<i32>,
+    pub reply_type: bool,
+    pub like_count: i32,
+    // whether the caller liked it, absent for anonymous callers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liked_by_me: Option<bool>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Reply {
+    pub async fn create(
+        pool: &MySqlPool,
+        comment_id: i32,
+        user_id: i32,
+        data: &CreateReply,
+    ) -> AppResult<u64> {
+        let last_id = sqlx::query!(
+            r#"
+                INSERT INTO reply(content, user_id, comment_id, reply_id, reply_type)
+                VALUES (?, ?, ?, ?, ?);
+            "#,
+            data.content,
+            user_id,
+            comment_id,
+            data.reply_id,
+            data.reply_id.is_some(),
+        )
+        .execute(pool)
+        .await?
+        .last_insert_id();
+
+        Ok(last_id)
+    }
+
+    /// The reply as seen by `viewer`
+    pub async fn find_by_id(
+        pool: &MySqlPool,
+        id: i32,
+        viewer: Option<i32>,
+    ) -> AppResult<Option<PublicReply>> {
+        let row = sqlx::query_as!(
+            PublicReply,
+            r#"
+                SELECT r.id, r.content, r.user_id, u.name as user_name, u.avatar as user_avatar,
+                    r.comment_id, r.reply_id, r.reply_type, r.like_count,
+                    IF(? IS NULL, NULL, EXISTS(SELECT 1 FROM reply_like l WHERE l.reply_id = r.id AND l.user_id = ?)) as `liked_by_me: bool`,
+                    r.created_at, r.updated_at
+                FROM reply r INNER JOIN user u ON u.id = r.user_id
+                WHERE r.id = ?
+            "#,
+            viewer,
+            viewer,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Replies of one comment thread, oldest first, flattened with `reply_id` as parent
+    pub async fn find_list(
+        pool: &MySqlPool,
+        comment_id: i32,
+        viewer: Option<i32>,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<PublicReply>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let rows = sqlx::query_as!(
+            PublicReply,
+            r#"
+                SELECT r.id, r.content, r.user_id, u.name as user_name, u.avatar as user_avatar,
+                    r.comment_id, r.reply_id, r.reply_type, r.like_count,
+                    IF(? IS NULL, NULL, EXISTS(SELECT 1 FROM reply_like l WHERE l.reply_id = r.id AND l.user_id = ?)) as `liked_by_me: bool`,
+                    r.created_at, r.updated_at
+                FROM reply r INNER JOIN user u ON u.id = r.user_id
+                WHERE r.comment_id = ?
+                ORDER BY r.created_at ASC, r.id ASC LIMIT ? OFFSET ?;
+            "#,
+            viewer,
+            viewer,
+            comment_id,
+            page_size,
+            page * page_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let row = sqlx::query!(
+            r#"SELECT count(*) as total FROM reply WHERE comment_id = ?;"#,
+            comment_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let pagination = PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: row.total as i32,
+            list: rows,
+        };
+
+        Ok(pagination)
+    }
+
+    /// The first `size` replies of each of the given comments, in a single query
+    pub async fn find_preview(
+        pool: &MySqlPool,
+        comment_ids: &[i32],
+        size: i64,
+        viewer: Option<i32>,
+    ) -> AppResult<Vec<PublicReply>> {
+        if comment_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut builder = QueryBuilder::new(
+            r#"
+                SELECT id, content, user_id, user_name, user_avatar, comment_id, reply_id,
+                    reply_type, like_count, liked_by_me, created_at, updated_at
+                FROM (
+                    SELECT r.id, r.content, r.user_id, u.name as user_name, u.avatar as user_avatar,
+                        r.comment_id, r.reply_id, r.reply_type, r.like_count, r.created_at, r.updated_at,
+                        IF(
+            "#,
+        );
+        builder.push_bind(viewer);
+        builder.push(
+            " IS NULL, NULL, EXISTS(SELECT 1 FROM reply_like l WHERE l.reply_id = r.id AND l.user_id = ",
+        );
+        builder.push_bind(viewer);
+        builder.push(
+            r#"
+                        )) as liked_by_me,
+                        ROW_NUMBER() OVER (PARTITION BY r.comment_id ORDER BY r.created_at, r.id) as row_num
+                    FROM reply r INNER JOIN user u ON u.id = r.user_id
+                    WHERE r.comment_id IN (
+            "#,
+        );
+        let mut ids = builder.separated(", ");
+        for id in comment_ids {
+            ids.push_bind(id);
+        }
+        builder.push(")) t WHERE row_num <= ");
+        builder.push_bind(size);
+        builder.push(" ORDER BY created_at, id");
+
+        let rows = builder
+            .build_query_as::<PublicReply>()
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn update(pool: &MySqlPool, id: i32, data: &UpdateReply) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE reply SET content = ? WHERE id = ?
+            "#,
+            data.content,
+            id,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows == 1)
+    }
+
+    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                delete from reply where id = ?
+            "#,
+            id
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(content: String) -> CreateReply {
+        CreateReply {
+            content,
+            reply_id: None,
+        }
+    }
+
+    #[test]
+    fn replies_need_content() {
+        assert!(reply(String::new()).validate().is_err());
+        assert!(reply(String::from("thanks")).validate().is_ok());
+        assert!(UpdateReply {
+            content: String::new()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn replies_are_limited_to_2000_characters() {
+        assert!(reply("a".repeat(2000)).validate().is_ok());
+        assert!(reply("a".repeat(2001)).validate().is_err());
+        let update = UpdateReply {
+            content: "a".repeat(2001),
+        };
+        assert!(update.validate().is_err());
+    }
+}
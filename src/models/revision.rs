@@ -0,0 +1,193 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{FromRow, MySql, MySqlPool, Transaction};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{Pagination, PaginationResponse},
+    errors::AppResult,
+};
+
+/// Snapshots of an article, one per save
+pub struct ArticleRevision;
+
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
+pub struct PublicRevision {
+    pub id: i32,
+    pub article_id: i32,
+    // absent once the editor's account was purged
+    pub user_id: Option<i32>,
+    pub user_name: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub summary: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A revision in the history list, without its content
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
+pub struct RevisionItem {
+    pub id: i32,
+    pub article_id: i32,
+    // absent once the editor's account was purged
+    pub user_id: Option<i32>,
+    pub user_name: Option<String>,
+    pub title: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl ArticleRevision {
+    /// Record the saved state of an article, as edited by `user_id`
+    pub async fn create(
+        tx: &mut Transaction<'_, MySql>,
+        article_id: i32,
+        user_id: i32,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO article_revision(article_id, user_id, title, content, summary)
+                SELECT id, ?, title, content, summary FROM article WHERE id = ?
+            "#,
+            user_id,
+            article_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Drop the oldest revisions of an article, keeping the latest `retention` ones
+    pub async fn prune(
+        tx: &mut Transaction<'_, MySql>,
+        article_id: i32,
+        retention: u32,
+    ) -> AppResult<()> {
+        // the oldest revision to keep
+        let oldest = sqlx::query!(
+            r#"
+                SELECT id FROM article_revision WHERE article_id = ?
+                ORDER BY id DESC LIMIT 1 OFFSET ?
+            "#,
+            article_id,
+            retention.max(1) - 1,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(oldest) = oldest {
+            sqlx::query!(
+                r#"
+                    delete from article_revision where article_id = ? AND id < ?
+                "#,
+                article_id,
+                oldest.id,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Record the current state of an article saved before revisions existed,
+    /// so its first edit can still be reverted
+    pub async fn create_initial(tx: &mut Transaction<'_, MySql>, article_id: i32) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO article_revision(article_id, user_id, title, content, summary, created_at)
+                SELECT id, user_id, title, content, summary, updated_at FROM article
+                WHERE id = ? AND NOT EXISTS (SELECT 1 FROM article_revision WHERE article_id = ?)
+            "#,
+            article_id,
+            article_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(
+        pool: &MySqlPool,
+        article_id: i32,
+        id: i32,
+    ) -> AppResult<Option<PublicRevision>> {
+        let row = sqlx::query_as!(
+            PublicRevision,
+            r#"
+                SELECT r.id, r.article_id, r.user_id, u.name as `user_name?`, r.title, r.content, r.summary, r.created_at
+                FROM article_revision r LEFT JOIN user u ON u.id = r.user_id
+                WHERE r.id = ? AND r.article_id = ?
+            "#,
+            id,
+            article_id,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// The revision matching the current state of the article
+    pub async fn find_latest(
+        pool: &MySqlPool,
+        article_id: i32,
+    ) -> AppResult<Option<PublicRevision>> {
+        let row = sqlx::query_as!(
+            PublicRevision,
+            r#"
+                SELECT r.id, r.article_id, r.user_id, u.name as `user_name?`, r.title, r.content, r.summary, r.created_at
+                FROM article_revision r LEFT JOIN user u ON u.id = r.user_id
+                WHERE r.article_id = ?
+                ORDER BY r.id DESC LIMIT 1
+            "#,
+            article_id,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Revisions of an article, newest first
+    pub async fn find_list(
+        pool: &MySqlPool,
+        article_id: i32,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<RevisionItem>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let rows = sqlx::query_as!(
+            RevisionItem,
+            r#"
+                SELECT r.id, r.article_id, r.user_id, u.name as `user_name?`, r.title, r.created_at
+                FROM article_revision r LEFT JOIN user u ON u.id = r.user_id
+                WHERE r.article_id = ?
+                ORDER BY r.id DESC LIMIT ? OFFSET ?;
+            "#,
+            article_id,
+            page_size,
+            page * page_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let row = sqlx::query!(
+            r#"SELECT count(*) as total FROM article_revision WHERE article_id = ?;"#,
+            article_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let pagination = PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: row.total as i32,
+            list: rows,
+        };
+
+        Ok(pagination)
+    }
+}
@@ -1,6 +1,8 @@
-use sqlx::FromRow;
+use sqlx::{FromRow, MySqlPool};
 
-#[derive(FromRow)]
+use crate::errors::AppResult;
+
+#[derive(FromRow, Debug)]
 pub struct Role {
     pub id: i32,
     pub name: String,
@@ -26,18 +28,35 @@ impl Role {
     //     Ok(())
     // }
 
-    // pub fn has_permission(self, perm: i32) -> bool {
-    //     (self.permissions & perm) == perm
-    // }
+    pub async fn find_by_user_id(pool: &MySqlPool, user_id: i32) -> AppResult<Option<Role>> {
+        let row = sqlx::query_as!(
+            Role,
+            r#"
+                SELECT role.id, role.name, role.`default`, role.permissions
+                FROM role INNER JOIN user ON user.role_id = role.id
+                WHERE user.id = ? AND user.deleted_at IS NULL;
+            "#,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub fn has_permission(&self, perm: Permission) -> bool {
+        let perm = perm as i32;
+        (self.permissions & perm) == perm
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Permission {
-    Follow,   // follow other user
-    Comment,  // comment other user's article
-    Write,    // write article
-    Moderate, // manager other comment
-    Admin,    // administrator
+    Follow = 1,   // follow other user
+    Comment = 2,  // comment other user's article
+    Write = 4,    // write article
+    Moderate = 8, // manager other comment
+    Admin = 16,   // administrator
 }
 
 impl Permission {
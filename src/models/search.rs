@@ -0,0 +1,87 @@
+use chrono::NaiveDateTime;
+use sqlx::{FromRow, MySql, MySqlPool, QueryBuilder};
+
+use crate::{errors::AppResult, models::article::ArticleStatus};
+
+/// An article as fed to the search index
+#[derive(FromRow, Debug)]
+pub struct SearchableArticle {
+    pub id: i32,
+    pub title: String,
+    pub slug: String,
+    pub summary: Option<String>,
+    pub content: String,
+    pub category_id: i32,
+    pub published_at: Option<NaiveDateTime>,
+    // published and not in the trash, others are taken out of the index
+    pub listed: bool,
+    // the content of a protected article is not indexed
+    pub protected: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+impl SearchableArticle {
+    pub async fn find_by_ids(pool: &MySqlPool, ids: &[i32]) -> AppResult<Vec<SearchableArticle>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut builder = Self::select();
+        builder.push(" WHERE id IN (");
+        let mut separated = builder.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        builder.push(")");
+
+        Self::fetch(pool, builder).await
+    }
+
+    /// Articles changed at or after `since`, trashed ones included
+    pub async fn find_updated_since(
+        pool: &MySqlPool,
+        since: NaiveDateTime,
+    ) -> AppResult<Vec<SearchableArticle>> {
+        let mut builder = Self::select();
+        builder.push(" WHERE updated_at >= ").push_bind(since);
+
+        Self::fetch(pool, builder).await
+    }
+
+    /// Up to `limit` published articles with an id above `after_id`, in id order
+    pub async fn find_listed_after(
+        pool: &MySqlPool,
+        after_id: i32,
+        limit: i64,
+    ) -> AppResult<Vec<SearchableArticle>> {
+        let mut builder = Self::select();
+        builder
+            .push(" WHERE status = ")
+            .push_bind(ArticleStatus::Published)
+            .push(" AND deleted_at IS NULL AND id > ")
+            .push_bind(after_id)
+            .push(" ORDER BY id LIMIT ")
+            .push_bind(limit);
+
+        Self::fetch(pool, builder).await
+    }
+
+    /// The columns the search index needs, to be followed by a `WHERE` clause
+    fn select<'a>() -> QueryBuilder<'a, MySql> {
+        let mut builder = QueryBuilder::new(
+            "SELECT id, title, slug, summary, content, category_id, published_at, (status = ",
+        );
+        builder.push_bind(ArticleStatus::Published).push(
+            " AND deleted_at IS NULL) as listed, password_hash IS NOT NULL as protected, updated_at FROM article",
+        );
+        builder
+    }
+
+    async fn fetch(
+        pool: &MySqlPool,
+        mut builder: QueryBuilder<'_, MySql>,
+    ) -> AppResult<Vec<SearchableArticle>> {
+        let rows = builder.build_query_as().fetch_all(pool).await?;
+        Ok(rows)
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::NaiveDateTime;
+use sqlx::{FromRow, MySqlPool};
+
+use crate::{errors::AppResult, models::article::ArticleStatus};
+
+/// A page listed in the sitemap, its path is relative to the site url
+#[derive(FromRow, Debug, Clone)]
+pub struct SitemapUrl {
+    pub path: String,
+    pub updated_at: NaiveDateTime,
+}
+
+pub struct Sitemap;
+
+impl Sitemap {
+    pub async fn count_articles(pool: &MySqlPool) -> AppResult<i64> {
+        let row = sqlx::query!(
+            r#"
+                SELECT count(*) as total FROM article WHERE status = ? AND deleted_at IS NULL
+            "#,
+            ArticleStatus::Published,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.total)
+    }
+
+    /// Published articles, oldest first so a page keeps its articles as new ones come
+    pub async fn find_articles(
+        pool: &MySqlPool,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<Vec<SitemapUrl>> {
+        let rows = sqlx::query_as!(
+            SitemapUrl,
+            r#"
+                SELECT CONCAT('/articles/', slug) as `path!: String`, updated_at FROM article
+                WHERE status = ? AND deleted_at IS NULL
+                ORDER BY id LIMIT ? OFFSET ?
+            "#,
+            ArticleStatus::Published,
+            limit,
+            offset,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Categories, tags and the authors of published articles, at most `limit` of them.
+    /// Authors are as recent as their latest article.
+    pub async fn find_pages(pool: &MySqlPool, limit: i64) -> AppResult<Vec<SitemapUrl>> {
+        let rows = sqlx::query_as!(
+            SitemapUrl,
+            r#"
+                (SELECT CONCAT('/categories/', id) as `path!: String`, updated_at FROM category
+                WHERE deleted_at IS NULL)
+                UNION ALL
+                (SELECT CONCAT('/tags/', id), updated_at FROM tag WHERE deleted_at IS NULL)
+                UNION ALL
+                (SELECT CONCAT('/users/', u.id), MAX(a.updated_at) FROM user u
+                INNER JOIN article a ON a.user_id = u.id
+                WHERE u.deleted_at IS NULL AND a.status = ? AND a.deleted_at IS NULL
+                GROUP BY u.id)
+                LIMIT ?
+            "#,
+            ArticleStatus::Published,
+            limit,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+}
@@ -0,0 +1,56 @@
+use sqlx::{MySql, MySqlPool, Transaction};
+
+use crate::errors::AppResult;
+
+/// Former slugs of renamed articles, so old links keep working
+pub struct SlugRedirect;
+
+impl SlugRedirect {
+    /// Point `slug` to the article, replacing any previous target
+    pub async fn create(
+        tx: &mut Transaction<'_, MySql>,
+        slug: &str,
+        article_id: i32,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO slug_redirect(slug, article_id) VALUES (?, ?)
+                ON DUPLICATE KEY UPDATE article_id = VALUES(article_id), created_at = NOW();
+            "#,
+            slug,
+            article_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The current slug of the article formerly known as `slug`
+    pub async fn find_target(pool: &MySqlPool, slug: &str) -> AppResult<Option<String>> {
+        let row = sqlx::query!(
+            r#"
+                SELECT a.slug FROM slug_redirect r INNER JOIN article a ON a.id = r.article_id
+                WHERE r.slug = ? AND a.deleted_at IS NULL
+            "#,
+            slug
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| r.slug))
+    }
+
+    pub async fn delete(tx: &mut Transaction<'_, MySql>, slug: &str) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                delete from slug_redirect where slug = ?
+            "#,
+            slug
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,10 +1,13 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, MySqlPool};
+use utoipa::ToSchema;
+use validator::Validate;
 
 use crate::{
     api::{Pagination, PaginationResponse},
     errors::AppResult,
+    models::article::ArticleStatus,
 };
 
 #[derive(FromRow)]
@@ -17,21 +20,43 @@ pub struct Tag {
     pub deleted_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct TagData {
+    #[validate(length(min = 1, max = 64, message = "must be 1-64 characters"))]
     pub name: String,
+    #[validate(length(max = 128, message = "must be at most 128 characters"))]
     pub description: Option<String>,
 }
 
-#[derive(FromRow, Debug, Clone, Serialize)]
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
 pub struct PublicTag {
     pub id: i32,
     pub name: String,
     pub description: Option<String>,
+    // published articles only
+    pub article_count: i64,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
 
+/// The tag fields embedded in article responses
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TagSummary {
+    pub id: i32,
+    pub name: String,
+}
+
+/// A tag in the trash
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
+pub struct TrashedTag {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub deleted_at: NaiveDateTime,
+}
+
 impl Tag {
     pub async fn create(pool: &MySqlPool, data: &TagData) -> AppResult<u64> {
         let last_id = sqlx::query_as!(
@@ -53,7 +78,14 @@ impl Tag {
     pub async fn find_by_id(pool: &MySqlPool, id: i32) -> AppResult<Option<PublicTag>> {
         let row = sqlx::query_as!(
             PublicTag,
-            "SELECT id, name, description, created_at, updated_at FROM tag WHERE id = ?",
+            r#"
+                SELECT id, name, description,
+                    (SELECT count(*) FROM article_tag INNER JOIN article ON article.id = article_tag.article_id
+                        WHERE article_tag.tag_id = tag.id AND article.status = ? AND article.deleted_at IS NULL) as `article_count!`,
+                    created_at, updated_at
+                FROM tag WHERE id = ? AND deleted_at IS NULL
+            "#,
+            ArticleStatus::Published,
             id
         )
         .fetch_optional(pool)
@@ -65,7 +97,14 @@ impl Tag {
     pub async fn find_by_name(pool: &MySqlPool, name: &str) -> AppResult<Option<PublicTag>> {
         let row = sqlx::query_as!(
             PublicTag,
-            "SELECT id, name, description, created_at, updated_at FROM tag WHERE name = ?",
+            r#"
+                SELECT id, name, description,
+                    (SELECT count(*) FROM article_tag INNER JOIN article ON article.id = article_tag.article_id
+                        WHERE article_tag.tag_id = tag.id AND article.status = ? AND article.deleted_at IS NULL) as `article_count!`,
+                    created_at, updated_at
+                FROM tag WHERE name = ? AND deleted_at IS NULL
+            "#,
+            ArticleStatus::Published,
             name
         )
         .fetch_optional(pool)
@@ -84,16 +123,21 @@ impl Tag {
         let rows = sqlx::query_as!(
             PublicTag,
             r#"
-                SELECT id, name, description, created_at, updated_at FROM tag
+                SELECT id, name, description,
+                    (SELECT count(*) FROM article_tag INNER JOIN article ON article.id = article_tag.article_id
+                        WHERE article_tag.tag_id = tag.id AND article.status = ? AND article.deleted_at IS NULL) as `article_count!`,
+                    created_at, updated_at
+                FROM tag WHERE deleted_at IS NULL
                 ORDER BY created_at DESC LIMIT ? OFFSET ?;
             "#,
+            ArticleStatus::Published,
             page_size,
             page * page_size,
         )
         .fetch_all(pool)
         .await?;
 
-        let row = sqlx::query!(r#"SELECT count(*) as total FROM user;"#)
+        let row = sqlx::query!(r#"SELECT count(*) as total FROM tag WHERE deleted_at IS NULL;"#)
             .fetch_one(pool)
             .await?;
 
@@ -113,7 +157,7 @@ impl Tag {
                 UPDATE tag SET
                     name = ?,
                     description = ?
-                WHERE id = ?
+                WHERE id = ? AND deleted_at IS NULL
             "#,
             data.name,
             data.description,
@@ -126,16 +170,100 @@ impl Tag {
         Ok(effect_rows == 1)
     }
 
-    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<()> {
-        sqlx::query!(
+    /// Move the tag to the trash
+    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE tag SET deleted_at = NOW() WHERE id = ? AND deleted_at IS NULL
+            "#,
+            id
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Whether a tag in the trash still holds the name
+    pub async fn exists_in_trash(pool: &MySqlPool, name: &str) -> AppResult<bool> {
+        let row = sqlx::query!(
+            r#"
+                SELECT count(*) as total FROM tag WHERE name = ? AND deleted_at IS NOT NULL;
+            "#,
+            name,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.total > 0)
+    }
+
+    pub async fn find_trash(
+        pool: &MySqlPool,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<TrashedTag>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let rows = sqlx::query_as!(
+            TrashedTag,
+            r#"
+                SELECT id, name, description, created_at, updated_at, deleted_at as `deleted_at!` FROM tag
+                WHERE deleted_at IS NOT NULL
+                ORDER BY deleted_at DESC LIMIT ? OFFSET ?;
+            "#,
+            page_size,
+            page * page_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let row =
+            sqlx::query!(r#"SELECT count(*) as total FROM tag WHERE deleted_at IS NOT NULL;"#)
+                .fetch_one(pool)
+                .await?;
+
+        let pagination = PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: row.total as i32,
+            list: rows,
+        };
+
+        Ok(pagination)
+    }
+
+    /// Take the tag out of the trash
+    pub async fn restore(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
             r#"
-                delete from tag where id = ?
+                UPDATE tag SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL
             "#,
             id
         )
         .execute(pool)
         .await?
         .rows_affected();
-        Ok(())
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Delete for good at most `limit` tags trashed before `before`
+    pub async fn purge(pool: &MySqlPool, before: NaiveDateTime, limit: i64) -> AppResult<u64> {
+        let effect_rows = sqlx::query!(
+            r#"
+                DELETE FROM tag
+                WHERE deleted_at < ?
+                LIMIT ?
+            "#,
+            before,
+            limit,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows)
     }
 }
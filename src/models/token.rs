@@ -0,0 +1,284 @@
+use chrono::NaiveDateTime;
+use sqlx::{FromRow, MySqlPool};
+
+use crate::errors::AppResult;
+
+#[derive(FromRow)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub family: String,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl RefreshToken {
+    pub async fn create(
+        pool: &MySqlPool,
+        user_id: i32,
+        token_hash: &str,
+        family: &str,
+        expires_at: NaiveDateTime,
+    ) -> AppResult<u64> {
+        let last_id = sqlx::query!(
+            r#"
+                INSERT INTO refresh_token(user_id, token_hash, family, expires_at)
+                VALUES (?, ?, ?, ?);
+            "#,
+            user_id,
+            token_hash,
+            family,
+            expires_at,
+        )
+        .execute(pool)
+        .await?
+        .last_insert_id();
+
+        Ok(last_id)
+    }
+
+    /// Exchange a refresh token for `new_hash` in the same family, returning the owner's id.
+    /// Returns `None` for unknown or expired tokens. Presenting an already rotated token
+    /// means it leaked, so the whole family is revoked.
+    pub async fn rotate(
+        pool: &MySqlPool,
+        token_hash: &str,
+        new_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> AppResult<Option<i32>> {
+        let mut tx = pool.begin().await?;
+
+        let token = sqlx::query_as!(
+            RefreshToken,
+            r#"
+                SELECT id, user_id, family, expires_at, revoked_at FROM refresh_token
+                WHERE token_hash = ? FOR UPDATE;
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let token = match token {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        if token.revoked_at.is_some() {
+            tracing::warn!(
+                "refresh token reuse detected, revoking family of user {}",
+                token.user_id
+            );
+            sqlx::query!(
+                r#"
+                    UPDATE refresh_token SET revoked_at = NOW()
+                    WHERE family = ? AND revoked_at IS NULL
+                "#,
+                token.family
+            )
+            .execute(&mut tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(None);
+        }
+
+        if token.expires_at <= chrono::Utc::now().naive_utc() {
+            return Ok(None);
+        }
+
+        let new_id = sqlx::query!(
+            r#"
+                INSERT INTO refresh_token(user_id, token_hash, family, expires_at)
+                VALUES (?, ?, ?, ?);
+            "#,
+            token.user_id,
+            new_hash,
+            token.family,
+            expires_at,
+        )
+        .execute(&mut tx)
+        .await?
+        .last_insert_id();
+
+        sqlx::query!(
+            r#"
+                UPDATE refresh_token SET revoked_at = NOW(), replaced_by = ?
+                WHERE id = ?
+            "#,
+            new_id,
+            token.id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(token.user_id))
+    }
+
+    /// Revoke every token in the family of the given token, i.e. one login session
+    pub async fn revoke_family(pool: &MySqlPool, user_id: i32, token_hash: &str) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                UPDATE refresh_token AS t
+                INNER JOIN refresh_token AS f ON t.family = f.family
+                SET t.revoked_at = NOW()
+                WHERE f.token_hash = ? AND t.user_id = ? AND t.revoked_at IS NULL
+            "#,
+            token_hash,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Revoke every refresh token of the user, i.e. all login sessions
+    pub async fn revoke_by_user(pool: &MySqlPool, user_id: i32) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                UPDATE refresh_token SET revoked_at = NOW()
+                WHERE user_id = ? AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+pub struct RevokedToken;
+
+impl RevokedToken {
+    /// Revoke an access token by its `jti` until it would expire anyway
+    pub async fn create(pool: &MySqlPool, jti: &str, expires_at: NaiveDateTime) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+                INSERT IGNORE INTO revoked_token(jti, expires_at)
+                VALUES (?, ?);
+            "#,
+            jti,
+            expires_at,
+        )
+        .execute(pool)
+        .await?;
+
+        // expired tokens are rejected by their `exp` claim, no need to keep them
+        sqlx::query!(
+            "DELETE FROM revoked_token WHERE expires_at < ?",
+            chrono::Utc::now().naive_utc()
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether the access token was revoked, either by its `jti` or because the user
+    /// changed password after it was issued or was deleted
+    pub async fn is_revoked(
+        pool: &MySqlPool,
+        jti: &str,
+        user_id: i32,
+        issued_at: NaiveDateTime,
+    ) -> AppResult<bool> {
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    (SELECT count(*) FROM revoked_token WHERE jti = ?) +
+                    (SELECT count(*) FROM user WHERE id = ? AND (password_changed_at > ? OR deleted_at IS NOT NULL))
+                as `total!`;
+            "#,
+            jti,
+            user_id,
+            issued_at,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.total > 0)
+    }
+}
+
+pub struct PasswordReset;
+
+impl PasswordReset {
+    /// Store a new reset token, tokens requested earlier can no longer be used
+    pub async fn create(
+        pool: &MySqlPool,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> AppResult<u64> {
+        sqlx::query!(
+            r#"
+                UPDATE password_reset SET used_at = NOW()
+                WHERE user_id = ? AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+
+        let last_id = sqlx::query!(
+            r#"
+                INSERT INTO password_reset(user_id, token_hash, expires_at)
+                VALUES (?, ?, ?);
+            "#,
+            user_id,
+            token_hash,
+            expires_at,
+        )
+        .execute(pool)
+        .await?
+        .last_insert_id();
+
+        Ok(last_id)
+    }
+
+    /// Whether the user requested a reset token less than `interval` ago
+    pub async fn requested_recently(
+        pool: &MySqlPool,
+        user_id: i32,
+        interval: chrono::Duration,
+    ) -> AppResult<bool> {
+        let threshold = (chrono::Utc::now() - interval).naive_utc();
+        let row = sqlx::query!(
+            r#"SELECT count(*) as total FROM password_reset WHERE user_id = ? AND created_at > ?;"#,
+            user_id,
+            threshold,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.total > 0)
+    }
+
+    /// Mark the token as used and return its user id, `None` if the token is unknown,
+    /// expired or already used
+    pub async fn consume(pool: &MySqlPool, token_hash: &str) -> AppResult<Option<i32>> {
+        let now = chrono::Utc::now().naive_utc();
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE password_reset SET used_at = NOW()
+                WHERE token_hash = ? AND used_at IS NULL AND expires_at > ?
+            "#,
+            token_hash,
+            now,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if effect_rows != 1 {
+            return Ok(None);
+        }
+
+        let row = sqlx::query!(
+            "SELECT user_id FROM password_reset WHERE token_hash = ?",
+            token_hash
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| r.user_id))
+    }
+}
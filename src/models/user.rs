@@ -1,11 +1,14 @@
 use crate::{
     api::{Pagination, PaginationResponse},
     errors::AppResult,
+    models::like::Like,
     utils::{avatar::get_avatar_url, hash::generate_hash},
 };
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, MySqlPool};
+use utoipa::ToSchema;
+use validator::Validate;
 
 #[derive(FromRow)]
 pub struct User {
@@ -21,21 +24,34 @@ pub struct User {
     pub is_active: Option<bool>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct CreateUser {
+    #[validate(length(min = 1, max = 64, message = "must be 1-64 characters"))]
     pub name: String,
+    #[validate(length(min = 6, max = 64, message = "must be 6-64 characters"))]
     pub password: String,
+    #[validate(email(message = "must be a valid email address"), length(max = 64))]
     pub email: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct UpdateUser {
+    #[validate(length(min = 1, max = 64, message = "must be 1-64 characters"))]
     pub name: String,
+    #[validate(email(message = "must be a valid email address"), length(max = 64))]
     pub email: String,
+    #[validate(url(message = "must be a valid url"), length(max = 128))]
     pub avatar: Option<String>,
 }
 
-#[derive(FromRow, Debug, Clone, Serialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdatePassword {
+    pub current_password: String,
+    #[validate(length(min = 6, max = 64, message = "must be 6-64 characters"))]
+    pub new_password: String,
+}
+
+#[derive(FromRow, Debug, Clone, Serialize, ToSchema)]
 pub struct PublicUser {
     pub id: i32,
     pub name: String,
@@ -46,6 +62,7 @@ pub struct PublicUser {
     pub created_at: NaiveDateTime,
     pub last_seen: NaiveDateTime,
     pub deleted_at: Option<NaiveDateTime>,
+    pub is_active: bool,
 }
 
 impl User {
@@ -76,7 +93,7 @@ impl User {
     pub async fn find_by_id(pool: &MySqlPool, id: i32) -> AppResult<Option<PublicUser>> {
         let row = sqlx::query_as!(
             PublicUser,
-            "SELECT id, name, email, password_hash, avatar, created_at, last_seen, deleted_at FROM user WHERE id = ?",
+            "SELECT id, name, email, password_hash, avatar, created_at, last_seen, deleted_at, is_active FROM user WHERE id = ? AND deleted_at IS NULL",
             id
         )
         .fetch_optional(pool)
@@ -93,8 +110,8 @@ impl User {
         let row = sqlx::query_as!(
             PublicUser,
             r#"
-                SELECT id, name, email, password_hash, avatar, created_at, last_seen, deleted_at
-                FROM user WHERE name = ? OR email = ?;
+                SELECT id, name, email, password_hash, avatar, created_at, last_seen, deleted_at, is_active
+                FROM user WHERE (name = ? OR email = ?) AND deleted_at IS NULL;
             "#,
             name,
             email,
@@ -139,7 +156,8 @@ impl User {
         let rows = sqlx::query_as!(
             PublicUser,
             r#"
-                SELECT id, name, email, password_hash, avatar, created_at, last_seen, deleted_at FROM user
+                SELECT id, name, email, password_hash, avatar, created_at, last_seen, deleted_at, is_active FROM user
+                WHERE deleted_at IS NULL
                 ORDER BY created_at DESC LIMIT ? OFFSET ?;
             "#,
             page_size,
@@ -148,7 +166,7 @@ impl User {
         .fetch_all(pool)
         .await?;
 
-        let row = sqlx::query!(r#"SELECT count(*) as total FROM user;"#)
+        let row = sqlx::query!(r#"SELECT count(*) as total FROM user WHERE deleted_at IS NULL;"#)
             .fetch_one(pool)
             .await?;
 
@@ -169,7 +187,7 @@ impl User {
                     name = ?,
                     email = ?,
                     avatar = ?
-                WHERE id = ?
+                WHERE id = ? AND deleted_at IS NULL
             "#,
             user_info.name,
             user_info.email,
@@ -183,16 +201,181 @@ impl User {
         Ok(effect_rows == 1)
     }
 
-    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<()> {
+    /// Set a new password, access tokens issued before are rejected from now on
+    pub async fn update_password(pool: &MySqlPool, id: i32, password: &str) -> AppResult<bool> {
+        let hash_password = generate_hash(password)?;
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE user SET
+                    password_hash = ?,
+                    password_changed_at = NOW()
+                WHERE id = ?
+            "#,
+            hash_password,
+            id,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows == 1)
+    }
+
+    pub async fn activate(pool: &MySqlPool, id: i32) -> AppResult<()> {
         sqlx::query!(
             r#"
-                delete from user where id = ?
+                UPDATE user SET is_active = 1 WHERE id = ?
+            "#,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record that a verification mail is being sent, returns false if the previous one
+    /// was sent less than `interval` ago
+    pub async fn mark_verification_sent(
+        pool: &MySqlPool,
+        id: i32,
+        interval: chrono::Duration,
+    ) -> AppResult<bool> {
+        let threshold = (chrono::Utc::now() - interval).naive_utc();
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE user SET verify_sent_at = NOW()
+                WHERE id = ? AND (verify_sent_at IS NULL OR verify_sent_at < ?)
+            "#,
+            id,
+            threshold,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Move the user to the trash, the account can no longer be used but is kept until
+    /// purged
+    pub async fn delete(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE user SET deleted_at = NOW() WHERE id = ? AND deleted_at IS NULL
             "#,
             id
         )
         .execute(pool)
         .await?
         .rows_affected();
-        Ok(())
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Whether a user in the trash still holds the name or the email
+    pub async fn exists_in_trash(pool: &MySqlPool, name: &str, email: &str) -> AppResult<bool> {
+        let row = sqlx::query!(
+            r#"
+                SELECT count(*) as total FROM user
+                WHERE (name = ? OR email = ?) AND deleted_at IS NOT NULL;
+            "#,
+            name,
+            email,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.total > 0)
+    }
+
+    pub async fn find_trash(
+        pool: &MySqlPool,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<PublicUser>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let rows = sqlx::query_as!(
+            PublicUser,
+            r#"
+                SELECT id, name, email, password_hash, avatar, created_at, last_seen, deleted_at, is_active FROM user
+                WHERE deleted_at IS NOT NULL
+                ORDER BY deleted_at DESC LIMIT ? OFFSET ?;
+            "#,
+            page_size,
+            page * page_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let row =
+            sqlx::query!(r#"SELECT count(*) as total FROM user WHERE deleted_at IS NOT NULL;"#)
+                .fetch_one(pool)
+                .await?;
+
+        let pagination = PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: row.total as i32,
+            list: rows,
+        };
+
+        Ok(pagination)
+    }
+
+    /// Take the user out of the trash
+    pub async fn restore(pool: &MySqlPool, id: i32) -> AppResult<bool> {
+        let effect_rows = sqlx::query!(
+            r#"
+                UPDATE user SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL
+            "#,
+            id
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(effect_rows == 1)
+    }
+
+    /// Delete for good at most `limit` users trashed before `before`, taking their likes
+    /// off the like counts. Authors are kept until their articles are purged.
+    pub async fn purge(pool: &MySqlPool, before: NaiveDateTime, limit: i64) -> AppResult<u64> {
+        let mut tx = pool.begin().await?;
+
+        // locked rows are being handled by another worker, skip them
+        let ids: Vec<i32> = sqlx::query!(
+            r#"
+                SELECT id FROM user
+                WHERE deleted_at < ? AND NOT EXISTS (SELECT 1 FROM article WHERE article.user_id = user.id)
+                LIMIT ?
+                FOR UPDATE SKIP LOCKED
+            "#,
+            before,
+            limit,
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|r| r.id)
+        .collect();
+
+        let mut effect_rows = 0;
+        for id in ids {
+            Like::remove_by_user(&mut tx, id).await?;
+            effect_rows += sqlx::query!(
+                r#"
+                    DELETE FROM user WHERE id = ?
+                "#,
+                id,
+            )
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+
+        Ok(effect_rows)
     }
 }
@@ -1,7 +1,8 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::http::StatusCode;
+use anyhow::Context;
 use axum::routing::get;
 use axum::Router;
 use sqlx::MySqlPool;
@@ -10,24 +11,117 @@ use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 
 use crate::api;
-use crate::errors::{AppResult, Error};
+use crate::errors::{self, Error};
+use crate::mailer::{self, Mailer};
+use crate::models::article::Article;
+use crate::search::{self, SearchIndex};
 use crate::settings::Settings;
+use crate::sitemap;
+use crate::utils::{markdown, throttle::Throttle};
+use crate::views::{self, ViewCounter};
+use crate::worker;
 
 pub struct AppState {
     pub pool: MySqlPool,
     pub secret: String,
+    pub access_token_ttl: chrono::Duration,
+    pub refresh_token_ttl: chrono::Duration,
+    pub verify_token_ttl: chrono::Duration,
+    pub reset_token_ttl: chrono::Duration,
+    pub unlock_token_ttl: chrono::Duration,
+    // wrong article passwords, per article and client address
+    pub unlock_throttle: Throttle<(i32, Option<IpAddr>)>,
+    pub site_url: String,
+    pub server_url: String,
+    pub password_reset_path: String,
+    pub site_title: String,
+    pub site_description: String,
+    pub feed_size: i32,
+    pub robots_txt: String,
+    pub search: Arc<SearchIndex>,
+    pub mailer: Arc<dyn Mailer>,
+    pub highlight_css: String,
+    pub revision_retention: u32,
+    pub views: Arc<ViewCounter>,
+    pub trust_proxy: bool,
 }
 
-pub async fn serve(settings: Settings, pool: MySqlPool) {
+pub async fn serve(settings: Settings, pool: MySqlPool) -> anyhow::Result<()> {
+    errors::set_debug(settings.debug);
+    let mailer = mailer::init(&settings.mail)?;
+    let highlight_css = markdown::highlight_css(&settings.highlight.theme).with_context(|| {
+        format!(
+            "unknown highlight.theme {}, available themes: {}",
+            settings.highlight.theme,
+            markdown::highlight_themes().join(", ")
+        )
+    })?;
+    let hashed = Article::hash_plain_passwords(&pool).await?;
+    if hashed > 0 {
+        tracing::info!("hashed {} article passwords stored in clear text", hashed);
+    }
+    if settings.worker.enabled {
+        worker::spawn(
+            pool.clone(),
+            settings.worker.interval,
+            settings.trash.retention,
+        );
+    }
+
+    let views = Arc::new(ViewCounter::new(
+        Duration::from_secs(settings.views.window * 60),
+        settings.views.max_readers,
+    ));
+    views::spawn(
+        Arc::clone(&views),
+        pool.clone(),
+        settings.views.flush_interval,
+    );
+
+    let search = Arc::new(SearchIndex::open(&settings.search.dir).context("open search index")?);
+    search.sync(&pool).await?;
+    search::spawn(
+        Arc::clone(&search),
+        pool.clone(),
+        settings.search.sync_interval,
+    );
+
+    let robots_txt = sitemap::render_robots(&settings.site.url, &settings.robots.disallow);
+
     let cors = CorsLayer::new().allow_origin(Any).allow_methods(Any);
     let app_state = Arc::new(AppState {
         pool,
         secret: settings.auth.secret,
+        access_token_ttl: chrono::Duration::minutes(settings.auth.access_token_expire),
+        refresh_token_ttl: chrono::Duration::days(settings.auth.refresh_token_expire),
+        verify_token_ttl: chrono::Duration::hours(settings.auth.verify_token_expire),
+        reset_token_ttl: chrono::Duration::minutes(settings.auth.reset_token_expire),
+        unlock_token_ttl: chrono::Duration::minutes(settings.auth.unlock_token_expire),
+        unlock_throttle: Throttle::new(
+            settings.auth.unlock_max_attempts,
+            Duration::from_secs(settings.auth.unlock_window * 60),
+        ),
+        site_url: settings.site.url,
+        server_url: settings.server.url,
+        password_reset_path: settings.site.password_reset_path,
+        site_title: settings.site.title,
+        site_description: settings.site.description,
+        feed_size: settings.feed.size,
+        robots_txt,
+        search,
+        mailer,
+        highlight_css,
+        revision_retention: settings.revision.retention,
+        views,
+        trust_proxy: settings.server.trust_proxy,
     });
 
     let app = Router::new()
         .route("/ping", get(ping))
         .nest("/api", api::create_route())
+        .merge(api::feed::create_route())
+        .merge(api::sitemap::create_route())
+        .merge(api::openapi::create_route())
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
@@ -40,18 +134,47 @@ pub async fn serve(settings: Settings, pool: MySqlPool) {
     tracing::info!("Listening on http://127.0.0.1:{}", settings.server.port);
 
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap()
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    // views buffered since the last flush would be lost otherwise
+    if let Err(e) = app_state.views.flush(&app_state.pool).await {
+        tracing::error!("flush article views failed: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Resolve on Ctrl+C or SIGTERM, letting in-flight requests finish
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutting down");
 }
 
 async fn ping() -> &'static str {
     "pong"
 }
 
-async fn handler_404() -> (StatusCode, AppResult<()>) {
-    (
-        StatusCode::NOT_FOUND,
-        Err(Error::NotFound(String::from(""))),
-    )
+async fn handler_404() -> Error {
+    Error::NotFound(String::from("resource"))
 }
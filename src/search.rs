@@ -0,0 +1,500 @@
+//! Full-text search over published articles, kept in an embedded tantivy index. Chinese
+//! text is segmented with jieba, other text is split into words.
+//!
+//! The index remembers up to which `updated_at` it has seen the database, and catches up
+//! from there on start and periodically afterwards, so changes made elsewhere, e.g. by a
+//! standalone worker, reach it too. Edits through the api are applied right away. Searches
+//! and writes keep a thread busy, they run on blocking threads off the async runtime.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use jieba_rs::{Jieba, TokenizeMode};
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+use tantivy::{
+    collector::{Count, TopDocs},
+    directory::MmapDirectory,
+    query::{BooleanQuery, Occur, Query, QueryParser, TermQuery},
+    schema::{
+        Field, IndexRecordOption, Schema, TantivyDocument, TextFieldIndexing, TextOptions, Value,
+        FAST, INDEXED, STORED,
+    },
+    tokenizer::{LowerCaser, RemoveLongFilter, TextAnalyzer, Token, TokenStream, Tokenizer},
+    DocAddress, Index, IndexReader, IndexWriter, ReloadPolicy, Score, SnippetGenerator, Term,
+};
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api::{Pagination, PaginationResponse},
+    errors::{AppResult, Error},
+    models::{article_tag::ArticleTag, search::SearchableArticle},
+};
+
+const TOKENIZER: &str = "jieba";
+// memory the index writer may use before flushing, in bytes
+const WRITER_MEMORY: usize = 50_000_000;
+// articles loaded at a time when rebuilding
+const REBUILD_BATCH: i64 = 500;
+// format of the `updated_at` the index has seen, kept as commit payload
+const SYNCED_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Query parameters of a search
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SearchQuery {
+    pub q: String,
+    pub category_id: Option<i32>,
+    pub tag_id: Option<i32>,
+}
+
+/// An article matching a search, best matches first. Highlights are html with the
+/// matched words in `<b>`, the rest escaped.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchHit {
+    pub id: i32,
+    pub slug: String,
+    pub title: String,
+    pub summary: Option<String>,
+    pub published_at: Option<NaiveDateTime>,
+    #[schema(value_type = f32)]
+    pub score: Score,
+    pub title_html: String,
+    pub snippet_html: String,
+}
+
+struct Fields {
+    id: Field,
+    slug: Field,
+    title: Field,
+    summary: Field,
+    content: Field,
+    category_id: Field,
+    tag_ids: Field,
+    published_at: Field,
+}
+
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+impl SearchIndex {
+    /// Open the index under `dir`, creating it when missing. Only one process may hold
+    /// it at a time.
+    pub fn open(dir: impl AsRef<Path>) -> tantivy::Result<Self> {
+        let mut builder = Schema::builder();
+        let text = TextOptions::default()
+            .set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer(TOKENIZER)
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            )
+            .set_stored();
+        let fields = Fields {
+            id: builder.add_u64_field("id", INDEXED | STORED | FAST),
+            slug: builder.add_text_field("slug", STORED),
+            title: builder.add_text_field("title", text.clone()),
+            summary: builder.add_text_field("summary", text.clone()),
+            content: builder.add_text_field("content", text),
+            category_id: builder.add_u64_field("category_id", INDEXED),
+            tag_ids: builder.add_u64_field("tag_ids", INDEXED),
+            published_at: builder.add_i64_field("published_at", STORED),
+        };
+        let schema = builder.build();
+
+        std::fs::create_dir_all(&dir)?;
+        let index = Index::open_or_create(MmapDirectory::open(dir)?, schema)?;
+        index
+            .tokenizers()
+            .register(TOKENIZER, analyzer(Arc::new(Jieba::new())));
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let writer = index.writer(WRITER_MEMORY)?;
+
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+        })
+    }
+
+    /// Bring the given articles up to date in the index, right after they were written
+    pub async fn refresh(self: &Arc<Self>, pool: &MySqlPool, ids: &[i32]) -> AppResult<()> {
+        let articles = SearchableArticle::find_by_ids(pool, ids).await?;
+        let docs = self.documents(pool, articles).await?;
+        let ids = ids.to_vec();
+        self.write(move |index, writer| {
+            // articles purged meanwhile are not found, they go too
+            index.delete(writer, ids);
+            index.add(writer, docs)?;
+            index.commit(writer, None)
+        })
+        .await
+    }
+
+    /// Index the articles changed since the last sync, or everything when the index is new
+    pub async fn sync(self: &Arc<Self>, pool: &MySqlPool) -> AppResult<()> {
+        let synced = self
+            .index
+            .load_metas()?
+            .payload
+            .and_then(|payload| NaiveDateTime::parse_from_str(&payload, SYNCED_FORMAT).ok());
+        let synced = match synced {
+            Some(synced) => synced,
+            None => return self.rebuild(pool).await,
+        };
+
+        let articles = SearchableArticle::find_updated_since(pool, synced).await?;
+        if articles.is_empty() {
+            return Ok(());
+        }
+        let latest = articles.iter().map(|a| a.updated_at).max();
+        let ids: Vec<i32> = articles.iter().map(|a| a.id).collect();
+        let docs = self.documents(pool, articles).await?;
+        self.write(move |index, writer| {
+            index.delete(writer, ids);
+            index.add(writer, docs)?;
+            index.commit(writer, latest.max(Some(synced)))
+        })
+        .await
+    }
+
+    /// Drop the whole index and index every published article again
+    pub async fn rebuild(self: &Arc<Self>, pool: &MySqlPool) -> AppResult<()> {
+        self.write(|_, writer| {
+            writer.delete_all_documents()?;
+            Ok(())
+        })
+        .await?;
+
+        let mut latest = None;
+        let mut after_id = 0;
+        loop {
+            let articles =
+                SearchableArticle::find_listed_after(pool, after_id, REBUILD_BATCH).await?;
+            let last = match articles.last() {
+                Some(last) => last.id,
+                None => break,
+            };
+            latest = latest.max(articles.iter().map(|a| a.updated_at).max());
+            let docs = self.documents(pool, articles).await?;
+            self.write(move |index, writer| index.add(writer, docs))
+                .await?;
+            after_id = last;
+        }
+        // with nothing published yet, the next sync simply rebuilds again
+        self.write(move |index, writer| index.commit(writer, latest))
+            .await
+    }
+
+    /// Run `f` with the index writer on a blocking thread
+    async fn write<T, F>(self: &Arc<Self>, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&Self, &mut IndexWriter) -> AppResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let index = Arc::clone(self);
+        blocking(move || {
+            let mut writer = index.writer.lock().unwrap();
+            f(&index, &mut writer)
+        })
+        .await
+    }
+
+    /// The documents of the listed ones of `articles` along with their tags
+    async fn documents(
+        &self,
+        pool: &MySqlPool,
+        articles: Vec<SearchableArticle>,
+    ) -> AppResult<Vec<TantivyDocument>> {
+        let articles: Vec<SearchableArticle> = articles.into_iter().filter(|a| a.listed).collect();
+        let ids: Vec<i32> = articles.iter().map(|a| a.id).collect();
+        let mut tags = ArticleTag::find_by_article_ids(pool, &ids).await?;
+
+        let docs = articles
+            .into_iter()
+            .map(|article| {
+                let mut doc = TantivyDocument::default();
+                doc.add_u64(self.fields.id, article.id as u64);
+                doc.add_text(self.fields.slug, &article.slug);
+                doc.add_text(self.fields.title, &article.title);
+                if let Some(summary) = &article.summary {
+                    doc.add_text(self.fields.summary, summary);
+                }
+                // nothing of a protected content may show up in results
+                if !article.protected {
+                    doc.add_text(self.fields.content, &article.content);
+                }
+                doc.add_u64(self.fields.category_id, article.category_id as u64);
+                for tag in tags.remove(&article.id).unwrap_or_default() {
+                    doc.add_u64(self.fields.tag_ids, tag.id as u64);
+                }
+                if let Some(published_at) = article.published_at {
+                    doc.add_i64(self.fields.published_at, published_at.timestamp());
+                }
+                doc
+            })
+            .collect();
+
+        Ok(docs)
+    }
+
+    /// Remove the articles from the index, uncommitted
+    fn delete(&self, writer: &IndexWriter, ids: Vec<i32>) {
+        for id in ids {
+            writer.delete_term(Term::from_field_u64(self.fields.id, id as u64));
+        }
+    }
+
+    /// Add the documents to the index, uncommitted
+    fn add(&self, writer: &IndexWriter, docs: Vec<TantivyDocument>) -> AppResult<()> {
+        for doc in docs {
+            writer.add_document(doc)?;
+        }
+        Ok(())
+    }
+
+    /// Commit pending changes, recording `synced` as the `updated_at` seen so far
+    fn commit(&self, writer: &mut IndexWriter, synced: Option<NaiveDateTime>) -> AppResult<()> {
+        let mut commit = writer.prepare_commit()?;
+        let payload = match synced {
+            Some(synced) => Some(synced.format(SYNCED_FORMAT).to_string()),
+            // keep the mark of the last sync
+            None => self.index.load_metas()?.payload,
+        };
+        if let Some(payload) = payload {
+            commit.set_payload(&payload);
+        }
+        commit.commit()?;
+
+        // make the changes visible to the next search
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    pub async fn search(
+        self: &Arc<Self>,
+        query: SearchQuery,
+        pagination: Pagination,
+    ) -> AppResult<PaginationResponse<SearchHit>> {
+        let index = Arc::clone(self);
+        blocking(move || index.find(&query, &pagination)).await
+    }
+
+    fn find(
+        &self,
+        query: &SearchQuery,
+        pagination: &Pagination,
+    ) -> AppResult<PaginationResponse<SearchHit>> {
+        let page = (pagination.page.unwrap_or(1) - 1).max(0);
+        let page_size: i32 = pagination.page_size.unwrap_or(10).max(1);
+
+        let mut parser = QueryParser::for_index(
+            &self.index,
+            vec![self.fields.title, self.fields.summary, self.fields.content],
+        );
+        parser.set_field_boost(self.fields.title, 3.0);
+        parser.set_field_boost(self.fields.summary, 2.0);
+        parser.set_conjunction_by_default();
+        // a stray quote or colon should not fail the search
+        let (text_query, _) = parser.parse_query_lenient(&query.q);
+
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, text_query.box_clone())];
+        if let Some(category_id) = query.category_id {
+            clauses.push((
+                Occur::Must,
+                self.term_query(self.fields.category_id, category_id),
+            ));
+        }
+        if let Some(tag_id) = query.tag_id {
+            clauses.push((Occur::Must, self.term_query(self.fields.tag_ids, tag_id)));
+        }
+        let search_query = BooleanQuery::new(clauses);
+
+        let searcher = self.reader.searcher();
+        let (top_docs, total) = searcher.search(
+            &search_query,
+            &(
+                TopDocs::with_limit(page_size as usize).and_offset((page * page_size) as usize),
+                Count,
+            ),
+        )?;
+
+        let mut title_snippets =
+            SnippetGenerator::create(&searcher, &*text_query, self.fields.title)?;
+        title_snippets.set_max_num_chars(256);
+        let mut content_snippets =
+            SnippetGenerator::create(&searcher, &*text_query, self.fields.content)?;
+        content_snippets.set_max_num_chars(160);
+
+        let mut list = Vec::with_capacity(top_docs.len());
+        for (score, address) in top_docs {
+            list.push(self.hit(
+                &searcher,
+                address,
+                score,
+                &title_snippets,
+                &content_snippets,
+            )?);
+        }
+
+        Ok(PaginationResponse {
+            page: page + 1,
+            page_size,
+            total: total as i32,
+            list,
+        })
+    }
+
+    fn term_query(&self, field: Field, id: i32) -> Box<dyn Query> {
+        Box::new(TermQuery::new(
+            Term::from_field_u64(field, id as u64),
+            IndexRecordOption::Basic,
+        ))
+    }
+
+    fn hit(
+        &self,
+        searcher: &tantivy::Searcher,
+        address: DocAddress,
+        score: Score,
+        title_snippets: &SnippetGenerator,
+        content_snippets: &SnippetGenerator,
+    ) -> AppResult<SearchHit> {
+        let doc: TantivyDocument = searcher.doc(address)?;
+        let text = |field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .map(String::from)
+        };
+
+        // snippets are empty when nothing matched in their field
+        let title = text(self.fields.title).unwrap_or_default();
+        let mut title_html = title_snippets.snippet_from_doc(&doc).to_html();
+        if title_html.is_empty() {
+            title_html = ammonia::clean_text(&title);
+        }
+        let mut snippet_html = content_snippets.snippet_from_doc(&doc).to_html();
+        if snippet_html.is_empty() {
+            snippet_html = ammonia::clean_text(&text(self.fields.summary).unwrap_or_default());
+        }
+
+        Ok(SearchHit {
+            id: doc
+                .get_first(self.fields.id)
+                .and_then(|v| v.as_u64())
+                .unwrap_or_default() as i32,
+            slug: text(self.fields.slug).unwrap_or_default(),
+            summary: text(self.fields.summary),
+            published_at: doc
+                .get_first(self.fields.published_at)
+                .and_then(|v| v.as_i64())
+                .and_then(|ts| NaiveDateTime::from_timestamp_opt(ts, 0)),
+            score,
+            title,
+            title_html,
+            snippet_html,
+        })
+    }
+}
+
+/// Run `f` on a blocking thread, a panic in it fails like an index error
+async fn blocking<T, F>(f: F) -> AppResult<T>
+where
+    F: FnOnce() -> AppResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Search(tantivy::TantivyError::ErrorInThread(e.to_string())))?
+}
+
+/// Catch up with the database every `interval` seconds, forever
+pub fn spawn(index: Arc<SearchIndex>, pool: MySqlPool, interval: u64) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = index.sync(&pool).await {
+                tracing::error!("sync search index failed: {}", e);
+            }
+        }
+    })
+}
+
+fn analyzer(jieba: Arc<Jieba>) -> TextAnalyzer {
+    TextAnalyzer::builder(JiebaTokenizer { jieba })
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+        .build()
+}
+
+/// Segments text with jieba in search mode, so long words are also found by their parts
+#[derive(Clone)]
+struct JiebaTokenizer {
+    jieba: Arc<Jieba>,
+}
+
+struct JiebaTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Tokenizer for JiebaTokenizer {
+    type TokenStream<'a> = JiebaTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> JiebaTokenStream {
+        // jieba counts offsets in characters, tantivy in bytes
+        let mut offsets: HashMap<usize, usize> = text
+            .char_indices()
+            .enumerate()
+            .map(|(c, (b, _))| (c, b))
+            .collect();
+        offsets.insert(text.chars().count(), text.len());
+
+        let tokens = self
+            .jieba
+            .tokenize(text, TokenizeMode::Search, true)
+            .into_iter()
+            // spaces and punctuation are no words
+            .filter(|t| t.word.chars().any(char::is_alphanumeric))
+            .enumerate()
+            .map(|(position, t)| Token {
+                offset_from: offsets[&t.start],
+                offset_to: offsets[&t.end],
+                position,
+                text: t.word.to_string(),
+                position_length: 1,
+            })
+            .collect();
+
+        JiebaTokenStream { tokens, index: 0 }
+    }
+}
+
+impl TokenStream for JiebaTokenStream {
+    fn advance(&mut self) -> bool {
+        self.index += 1;
+        self.index <= self.tokens.len()
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
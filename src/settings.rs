@@ -6,6 +6,11 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct Server {
     pub port: u16,
+    // public base url of this server
+    pub url: String,
+    // take the client address from the last `X-Forwarded-For` entry, the one appended by
+    // the reverse proxy, enable only behind one
+    pub trust_proxy: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,6 +26,102 @@ pub struct Logger {
 #[derive(Debug, Deserialize)]
 pub struct Auth {
     pub secret: String,
+    // access token lifetime, in minutes
+    pub access_token_expire: i64,
+    // refresh token lifetime, in days
+    pub refresh_token_expire: i64,
+    // email verification link lifetime, in hours
+    pub verify_token_expire: i64,
+    // password reset token lifetime, in minutes
+    pub reset_token_expire: i64,
+    // lifetime of a token unlocking a protected article, in minutes
+    pub unlock_token_expire: i64,
+    // wrong article passwords allowed per article and client within the window
+    pub unlock_max_attempts: u32,
+    // in minutes
+    pub unlock_window: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Site {
+    // public base url, used to build links sent to users
+    pub url: String,
+    // page under `url` linked from password reset mails
+    pub password_reset_path: String,
+    // shown in feeds
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Mail {
+    // smtp, file or log
+    pub backend: String,
+    pub from: String,
+    // output directory of the file backend
+    pub dir: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub smtp_starttls: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Highlight {
+    // syntect theme of code blocks, e.g. InspiredGitHub, base16-ocean.dark
+    pub theme: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Revision {
+    // revisions kept per article, older ones are dropped
+    pub retention: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Trash {
+    // days a deleted item stays in the trash before it is purged, 0 keeps it forever
+    pub retention: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Views {
+    // minutes during which a reader is counted once per article
+    pub window: u64,
+    // seconds between two writes of the buffered views
+    pub flush_interval: u64,
+    // readers remembered within the window, the oldest are forgotten beyond it and may be
+    // counted again
+    pub max_readers: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Worker {
+    // run the background jobs inside the http server
+    pub enabled: bool,
+    // seconds between two runs of the jobs
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Feed {
+    // latest published articles listed in a feed
+    pub size: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Robots {
+    // paths crawlers are asked to stay away from
+    pub disallow: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Search {
+    // directory of the search index
+    pub dir: String,
+    // seconds between two catch-ups of the index with the database
+    pub sync_interval: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,6 +131,16 @@ pub struct Settings {
     pub database: Database,
     pub logger: Logger,
     pub auth: Auth,
+    pub site: Site,
+    pub mail: Mail,
+    pub worker: Worker,
+    pub highlight: Highlight,
+    pub revision: Revision,
+    pub trash: Trash,
+    pub views: Views,
+    pub feed: Feed,
+    pub robots: Robots,
+    pub search: Search,
 }
 
 pub fn init() -> Result<Settings, ConfigError> {
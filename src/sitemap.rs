@@ -0,0 +1,153 @@
+//! Sitemaps and robots.txt for search engines. A sitemap holds at most 50,000 urls, past
+//! that `/sitemap.xml` becomes an index of smaller ones: `/sitemaps/pages.xml` for
+//! categories, tags and authors, and `/sitemaps/articles/<n>.xml` for articles.
+
+use std::path::{Path, PathBuf};
+
+use chrono::SecondsFormat;
+use sqlx::MySqlPool;
+
+use crate::{
+    errors::AppResult,
+    feed::utc,
+    models::sitemap::{Sitemap, SitemapUrl},
+};
+
+// urls allowed in a single sitemap
+pub const MAX_URLS: i64 = 50_000;
+
+/// `/sitemap.xml`, listing everything when it fits and an index of sitemaps otherwise
+pub async fn render_root(pool: &MySqlPool, site_url: &str) -> AppResult<String> {
+    let articles = Sitemap::count_articles(pool).await?;
+    let mut pages = Sitemap::find_pages(pool, MAX_URLS).await?;
+    if fits(articles, pages.len()) {
+        pages.extend(Sitemap::find_articles(pool, MAX_URLS, 0).await?);
+        return Ok(urlset(site_url, true, &pages));
+    }
+
+    let mut sitemaps = vec![format!("{}/sitemaps/pages.xml", site_url)];
+    for page in 1..=article_pages(articles) {
+        sitemaps.push(format!("{}/sitemaps/articles/{}.xml", site_url, page));
+    }
+    Ok(index(&sitemaps))
+}
+
+/// The home page, categories, tags and authors
+pub async fn render_pages(pool: &MySqlPool, site_url: &str) -> AppResult<String> {
+    let pages = Sitemap::find_pages(pool, MAX_URLS - 1).await?;
+    Ok(urlset(site_url, true, &pages))
+}
+
+/// The `page`th 50,000 published articles, `None` past the last one
+pub async fn render_articles(
+    pool: &MySqlPool,
+    site_url: &str,
+    page: i64,
+) -> AppResult<Option<String>> {
+    let articles = Sitemap::count_articles(pool).await?;
+    if page < 1 || page > article_pages(articles) {
+        return Ok(None);
+    }
+
+    let urls = Sitemap::find_articles(pool, MAX_URLS, (page - 1) * MAX_URLS).await?;
+    Ok(Some(urlset(site_url, false, &urls)))
+}
+
+/// Ask crawlers to stay away from `disallow` and point them to the sitemap
+pub fn render_robots(site_url: &str, disallow: &[String]) -> String {
+    let mut robots = String::from("User-agent: *\n");
+    if disallow.is_empty() {
+        // an empty rule allows everything
+        robots.push_str("Disallow:\n");
+    }
+    for path in disallow {
+        robots.push_str(&format!("Disallow: {}\n", path));
+    }
+    robots.push_str(&format!("\nSitemap: {}/sitemap.xml\n", site_url));
+    robots
+}
+
+/// Write robots.txt and the sitemaps under `dir` for static hosting, returning the
+/// written files
+pub async fn write(
+    pool: &MySqlPool,
+    site_url: &str,
+    disallow: &[String],
+    dir: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![
+        (dir.join("robots.txt"), render_robots(site_url, disallow)),
+        (dir.join("sitemap.xml"), render_root(pool, site_url).await?),
+    ];
+    let articles = Sitemap::count_articles(pool).await?;
+    let pages = Sitemap::find_pages(pool, MAX_URLS).await?;
+    if !fits(articles, pages.len()) {
+        let sitemaps = dir.join("sitemaps");
+        files.push((
+            sitemaps.join("pages.xml"),
+            render_pages(pool, site_url).await?,
+        ));
+        for page in 1..=article_pages(articles) {
+            if let Some(xml) = render_articles(pool, site_url, page).await? {
+                files.push((sitemaps.join("articles").join(format!("{}.xml", page)), xml));
+            }
+        }
+    }
+
+    for (path, content) in &files {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, content).await?;
+    }
+
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Whether all urls fit in `/sitemap.xml`, the home page is not in the database
+fn fits(articles: i64, pages: usize) -> bool {
+    articles + (pages as i64) < MAX_URLS
+}
+
+fn article_pages(articles: i64) -> i64 {
+    (articles + MAX_URLS - 1) / MAX_URLS
+}
+
+/// A `<urlset>` of `urls`, preceded by the home page when `home` is set
+fn urlset(site_url: &str, home: bool, urls: &[SitemapUrl]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    if home {
+        xml.push_str(&format!("<url><loc>{}/</loc></url>\n", escape(site_url)));
+    }
+    for url in urls {
+        xml.push_str(&format!(
+            "<url><loc>{}{}</loc><lastmod>{}</lastmod></url>\n",
+            escape(site_url),
+            escape(&url.path),
+            utc(url.updated_at).to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+fn index(sitemaps: &[String]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for loc in sitemaps {
+        xml.push_str(&format!("<sitemap><loc>{}</loc></sitemap>\n", escape(loc)));
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
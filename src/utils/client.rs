@@ -0,0 +1,106 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+
+use crate::{router::AppState, utils::hash::hash_token};
+
+/// Address and user agent of the client, e.g. telling anonymous readers apart. Behind a
+/// trusted reverse proxy the address is the one it forwarded.
+pub struct Client {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl Client {
+    /// Signed-in readers are known by id, others by a hash of their address and user
+    /// agent so no address is kept in memory
+    pub fn viewer_key(&self, user_id: Option<i32>) -> String {
+        if let Some(user_id) = user_id {
+            return format!("user:{}", user_id);
+        }
+
+        let ip = self.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        let user_agent = self.user_agent.as_deref().unwrap_or_default();
+        format!("client:{}", hash_token(&format!("{}\n{}", ip, user_agent)))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Client
+where
+    Arc<AppState>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = Arc::<AppState>::from_ref(state);
+
+        let forwarded = if state.trust_proxy {
+            forwarded_ip(&parts.headers)
+        } else {
+            None
+        };
+        let ip = forwarded.or_else(|| {
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        });
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+
+        Ok(Client { ip, user_agent })
+    }
+}
+
+/// The address the trusted proxy appended to `X-Forwarded-For`. Entries on its left are
+/// sent by the client and can be anything.
+fn forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .next_back()
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn the_address_added_by_the_proxy_is_used() {
+        let ip = forwarded_ip(&headers(&["1.1.1.1, 10.0.0.1", "203.0.113.7"]));
+        assert_eq!(ip, "203.0.113.7".parse().ok());
+
+        let ip = forwarded_ip(&headers(&["spoofed, 203.0.113.7"]));
+        assert_eq!(ip, "203.0.113.7".parse().ok());
+    }
+
+    #[test]
+    fn missing_or_invalid_addresses_are_ignored() {
+        assert_eq!(forwarded_ip(&headers(&[])), None);
+        assert_eq!(forwarded_ip(&headers(&["203.0.113.7, unknown"])), None);
+    }
+}
@@ -0,0 +1,91 @@
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use utoipa::ToSchema;
+
+// unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DiffLine {
+    // equal, insert or delete
+    pub op: &'static str,
+    // 1-based line numbers in the old and new text
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub content: String,
+}
+
+/// Line-level diff of two texts, as hunks of changed lines with some context
+pub fn line_diff(old: &str, new: &str) -> Vec<Vec<DiffLine>> {
+    let diff = TextDiff::from_lines(old, new);
+
+    diff.grouped_ops(CONTEXT_LINES)
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| DiffLine {
+                    op: match change.tag() {
+                        ChangeTag::Equal => "equal",
+                        ChangeTag::Insert => "insert",
+                        ChangeTag::Delete => "delete",
+                    },
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    content: change.value().trim_end_matches('\n').to_string(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("line {}", i)).collect()
+    }
+
+    #[test]
+    fn equal_texts_have_no_hunks() {
+        assert!(line_diff("a\nb\n", "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn changes_carry_their_line_numbers() {
+        let hunks = line_diff("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!(hunks.len(), 1);
+        let ops: Vec<(&str, Option<usize>, Option<usize>, &str)> = hunks[0]
+            .iter()
+            .map(|l| (l.op, l.old_line, l.new_line, l.content.as_str()))
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                ("equal", Some(1), Some(1), "a"),
+                ("delete", Some(2), None, "b"),
+                ("insert", None, Some(2), "B"),
+                ("equal", Some(3), Some(3), "c"),
+                ("insert", None, Some(4), "d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn distant_changes_are_split_into_hunks_with_context() {
+        let old = lines(20);
+        let mut new = old.clone();
+        new[1] = String::from("changed 2");
+        new[18] = String::from("changed 19");
+
+        let hunks = line_diff(&old.join("\n"), &new.join("\n"));
+        assert_eq!(hunks.len(), 2);
+        // one line before the change is all there is, three after it
+        assert_eq!(hunks[0].first().unwrap().old_line, Some(1));
+        assert_eq!(hunks[0].last().unwrap().old_line, Some(5));
+        assert_eq!(hunks[1].first().unwrap().old_line, Some(16));
+        assert_eq!(hunks[1].last().unwrap().old_line, Some(20));
+    }
+}
@@ -1,5 +1,6 @@
 use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use rand_core::OsRng;
+use blake2::{Blake2s256, Digest};
+use rand_core::{OsRng, RngCore};
 
 use crate::errors::{AppResult, Error};
 
@@ -24,3 +25,17 @@ pub fn verify_password(password: &str, password_hash: &str) -> AppResult<bool> {
         .verify_password(password.as_bytes(), &password_hash)
         .is_ok())
 }
+
+/// Generate a random hex token of `len` bytes, used for opaque tokens and ids
+pub fn generate_token(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash an opaque token for storage, tokens are random so no salt is needed
+pub fn hash_token(token: &str) -> String {
+    let mut hasher = Blake2s256::new();
+    hasher.update(token.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
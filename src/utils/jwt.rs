@@ -12,8 +12,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     errors::{AppResult, AuthError, Error},
-    models::user::PublicUser,
+    models::{token::RevokedToken, user::PublicUser},
     router::AppState,
+    utils::hash::{generate_token, hash_token},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,6 +52,13 @@ where
         let token_data = decode(bearer.token(), &state.secret)
             .map_err(|_| Error::Auth(AuthError::InvalidToken))?;
 
+        let claims = &token_data.claims;
+        if RevokedToken::is_revoked(&state.pool, &claims.jti, claims.user.id, claims.issued_at())
+            .await?
+        {
+            return Err(Error::Auth(AuthError::InvalidToken));
+        }
+
         Ok(token_data.claims)
     }
 }
@@ -59,22 +67,32 @@ where
 pub struct Claims {
     pub exp: usize, // Expiration time (as UTC timestamp). validate_exp defaults to true in validation
     pub iat: usize, // Issued at (as UTC timestamp)
+    pub jti: String, // Token id, checked against revoked tokens
     pub user: AuthToken,
 }
 
 impl Claims {
-    pub fn new(user: PublicUser) -> Self {
+    pub fn new(user: PublicUser, ttl: chrono::Duration) -> Self {
         Self {
-            exp: (chrono::Local::now() + chrono::Duration::days(30)).timestamp() as usize,
+            exp: (chrono::Local::now() + ttl).timestamp() as usize,
             iat: chrono::Local::now().timestamp() as usize,
+            jti: generate_token(16),
             user: AuthToken::from(user),
         }
     }
+
+    pub fn expires_at(&self) -> chrono::NaiveDateTime {
+        chrono::NaiveDateTime::from_timestamp_opt(self.exp as i64, 0).unwrap_or_default()
+    }
+
+    pub fn issued_at(&self) -> chrono::NaiveDateTime {
+        chrono::NaiveDateTime::from_timestamp_opt(self.iat as i64, 0).unwrap_or_default()
+    }
 }
 
-pub fn encode(user: PublicUser, secret: &str) -> AppResult<String> {
+pub fn encode(user: PublicUser, secret: &str, ttl: chrono::Duration) -> AppResult<String> {
     let encoding_key = EncodingKey::from_secret(secret.as_ref());
-    let claims = Claims::new(user);
+    let claims = Claims::new(user, ttl);
 
     jsonwebtoken::encode(&Header::default(), &claims, &encoding_key)
         .map_err(|_| Error::Auth(AuthError::TokenCreation))
@@ -86,3 +104,185 @@ pub fn decode(token: &str, secret: &str) -> AppResult<TokenData<Claims>> {
     jsonwebtoken::decode(token, &decoding_key, &Validation::default())
         .map_err(|_| Error::Auth(AuthError::InvalidToken))
 }
+
+/// Claims of single purpose tokens sent to users, e.g. email verification links
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActionClaims {
+    pub exp: usize,
+    pub iat: usize,
+    pub sub: i32, // User id
+    pub email: String,
+    pub action: String,
+}
+
+pub fn encode_action(
+    user: &PublicUser,
+    action: &str,
+    secret: &str,
+    ttl: chrono::Duration,
+) -> AppResult<String> {
+    let encoding_key = EncodingKey::from_secret(secret.as_ref());
+    let claims = ActionClaims {
+        exp: (chrono::Local::now() + ttl).timestamp() as usize,
+        iat: chrono::Local::now().timestamp() as usize,
+        sub: user.id,
+        email: user.email.clone(),
+        action: action.to_string(),
+    };
+
+    jsonwebtoken::encode(&Header::default(), &claims, &encoding_key)
+        .map_err(|_| Error::Auth(AuthError::TokenCreation))
+}
+
+pub fn decode_action(token: &str, action: &str, secret: &str) -> AppResult<ActionClaims> {
+    let decoding_key = DecodingKey::from_secret(secret.as_ref());
+
+    let token_data =
+        jsonwebtoken::decode::<ActionClaims>(token, &decoding_key, &Validation::default())
+            .map_err(|_| Error::Auth(AuthError::InvalidToken))?;
+    if token_data.claims.action != action {
+        return Err(Error::Auth(AuthError::InvalidToken));
+    }
+
+    Ok(token_data.claims)
+}
+
+/// Claims of a token opening a password protected article. It stops working when the
+/// password changes, `key` is derived from the password hash.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnlockClaims {
+    pub exp: usize,
+    pub iat: usize,
+    pub sub: i32, // Article id
+    pub key: String,
+}
+
+fn unlock_key(password_hash: &str) -> String {
+    hash_token(password_hash)[..16].to_string()
+}
+
+pub fn encode_unlock(
+    article_id: i32,
+    password_hash: &str,
+    secret: &str,
+    ttl: chrono::Duration,
+) -> AppResult<String> {
+    let encoding_key = EncodingKey::from_secret(secret.as_ref());
+    let claims = UnlockClaims {
+        exp: (chrono::Local::now() + ttl).timestamp() as usize,
+        iat: chrono::Local::now().timestamp() as usize,
+        sub: article_id,
+        key: unlock_key(password_hash),
+    };
+
+    jsonwebtoken::encode(&Header::default(), &claims, &encoding_key)
+        .map_err(|_| Error::Auth(AuthError::TokenCreation))
+}
+
+pub fn decode_unlock(
+    token: &str,
+    article_id: i32,
+    password_hash: &str,
+    secret: &str,
+) -> AppResult<UnlockClaims> {
+    let decoding_key = DecodingKey::from_secret(secret.as_ref());
+
+    let token_data =
+        jsonwebtoken::decode::<UnlockClaims>(token, &decoding_key, &Validation::default())
+            .map_err(|_| Error::Auth(AuthError::InvalidToken))?;
+    if token_data.claims.sub != article_id || token_data.claims.key != unlock_key(password_hash) {
+        return Err(Error::Auth(AuthError::InvalidToken));
+    }
+
+    Ok(token_data.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test secret";
+
+    fn user() -> PublicUser {
+        PublicUser {
+            id: 7,
+            name: String::from("alice"),
+            email: String::from("alice@example.com"),
+            password_hash: String::new(),
+            avatar: None,
+            created_at: chrono::NaiveDateTime::default(),
+            last_seen: chrono::NaiveDateTime::default(),
+            deleted_at: None,
+            is_active: true,
+        }
+    }
+
+    #[test]
+    fn access_tokens_round_trip() {
+        let token = encode(user(), SECRET, chrono::Duration::minutes(15)).unwrap();
+        let claims = decode(&token, SECRET).unwrap().claims;
+        assert_eq!(claims.user.id, 7);
+        assert_eq!(claims.user.email, "alice@example.com");
+        assert!(claims.expires_at() > claims.issued_at());
+    }
+
+    #[test]
+    fn access_tokens_are_unique() {
+        let ttl = chrono::Duration::minutes(15);
+        let first = decode(&encode(user(), SECRET, ttl).unwrap(), SECRET).unwrap();
+        let second = decode(&encode(user(), SECRET, ttl).unwrap(), SECRET).unwrap();
+        assert_ne!(first.claims.jti, second.claims.jti);
+    }
+
+    #[test]
+    fn access_tokens_of_another_secret_are_rejected() {
+        let token = encode(user(), "another secret", chrono::Duration::minutes(15)).unwrap();
+        assert!(decode(&token, SECRET).is_err());
+    }
+
+    #[test]
+    fn expired_access_tokens_are_rejected() {
+        // past the default leeway of a minute
+        let token = encode(user(), SECRET, chrono::Duration::minutes(-5)).unwrap();
+        assert!(decode(&token, SECRET).is_err());
+    }
+
+    #[test]
+    fn action_tokens_round_trip() {
+        let token = encode_action(&user(), "verify", SECRET, chrono::Duration::hours(24)).unwrap();
+        let claims = decode_action(&token, "verify", SECRET).unwrap();
+        assert_eq!(claims.sub, 7);
+        assert_eq!(claims.email, "alice@example.com");
+    }
+
+    #[test]
+    fn action_tokens_are_rejected_for_another_action() {
+        let token = encode_action(&user(), "verify", SECRET, chrono::Duration::hours(24)).unwrap();
+        assert!(decode_action(&token, "reset", SECRET).is_err());
+    }
+
+    #[test]
+    fn access_tokens_are_no_action_tokens() {
+        let token = encode(user(), SECRET, chrono::Duration::minutes(15)).unwrap();
+        assert!(decode_action(&token, "verify", SECRET).is_err());
+    }
+
+    #[test]
+    fn unlock_tokens_round_trip() {
+        let token = encode_unlock(3, "hash", SECRET, chrono::Duration::hours(1)).unwrap();
+        let claims = decode_unlock(&token, 3, "hash", SECRET).unwrap();
+        assert_eq!(claims.sub, 3);
+    }
+
+    #[test]
+    fn unlock_tokens_are_rejected_for_another_article() {
+        let token = encode_unlock(3, "hash", SECRET, chrono::Duration::hours(1)).unwrap();
+        assert!(decode_unlock(&token, 4, "hash", SECRET).is_err());
+    }
+
+    #[test]
+    fn unlock_tokens_are_rejected_after_a_password_change() {
+        let token = encode_unlock(3, "old hash", SECRET, chrono::Duration::hours(1)).unwrap();
+        assert!(decode_unlock(&token, 3, "new hash", SECRET).is_err());
+    }
+}
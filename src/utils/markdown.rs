@@ -0,0 +1,445 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use ammonia::Builder;
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag};
+use serde::Serialize;
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+use utoipa::ToSchema;
+
+// characters kept when the summary is taken from the first paragraph
+const EXCERPT_LENGTH: usize = 200;
+// reading speed, in words per minute for latin text and characters per minute for CJK text
+const WORDS_PER_MINUTE: usize = 200;
+const CJK_CHARS_PER_MINUTE: usize = 300;
+// highlighted tokens get classes like `hl-keyword`, matching `highlight_css`
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+
+/// A heading of the table of contents, `anchor` is the id of the heading in the rendered html
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TocItem {
+    pub level: u32,
+    pub anchor: String,
+    pub title: String,
+    pub children: Vec<TocItem>,
+}
+
+/// Structure and statistics of an article's markdown content
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Outline {
+    pub toc: Vec<TocItem>,
+    pub word_count: usize,
+    // estimated, in minutes
+    pub reading_time: usize,
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+}
+
+/// Render markdown (CommonMark with GFM tables, footnotes, strikethrough and task lists)
+/// to HTML which is safe to embed in a page. Headings get the anchors listed in the
+/// outline's table of contents, fenced code is highlighted.
+pub fn render(content: &str) -> String {
+    let events: Vec<Event> = Parser::new_ext(content, options()).collect();
+    let anchors: Vec<String> = headings(&events).into_iter().map(|h| h.anchor).collect();
+
+    let mut anchors = anchors.iter();
+    // source of the fenced code block being read
+    let mut code: Option<String> = None;
+    let events = events.into_iter().filter_map(|event| match event {
+        Event::Start(Tag::Heading(level, _, classes)) => Some(Event::Start(Tag::Heading(
+            level,
+            anchors.next().map(String::as_str),
+            classes,
+        ))),
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))) => {
+            code = Some(String::new());
+            None
+        }
+        Event::Text(text) if code.is_some() => {
+            code.as_mut().unwrap().push_str(&text);
+            None
+        }
+        Event::End(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
+            let source = code.take().unwrap_or_default();
+            Some(Event::Html(highlight(&lang, &source).into()))
+        }
+        event => Some(event),
+    });
+
+    let mut unsafe_html = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, events);
+
+    sanitize(&unsafe_html)
+}
+
+/// Table of contents, word count and reading time of the content
+pub fn outline(content: &str) -> Outline {
+    let events: Vec<Event> = Parser::new_ext(content, options()).collect();
+
+    let mut text = String::new();
+    for event in &events {
+        match event {
+            Event::Text(t) | Event::Code(t) => {
+                text.push_str(t);
+                text.push(' ');
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    let (words, cjk_chars) = count_words(&text);
+    let minutes =
+        words as f64 / WORDS_PER_MINUTE as f64 + cjk_chars as f64 / CJK_CHARS_PER_MINUTE as f64;
+
+    Outline {
+        toc: nest(headings(&events)),
+        word_count: words + cjk_chars,
+        reading_time: (minutes.ceil() as usize).max(1),
+    }
+}
+
+/// Plain text of the first paragraph, shortened to fit a summary
+pub fn excerpt(content: &str) -> Option<String> {
+    let mut text = String::new();
+    let mut in_paragraph = false;
+    for event in Parser::new_ext(content, options()) {
+        match event {
+            Event::Start(Tag::Paragraph) => in_paragraph = true,
+            Event::End(Tag::Paragraph) if !text.trim().is_empty() => break,
+            Event::End(Tag::Paragraph) => in_paragraph = false,
+            Event::Text(t) | Event::Code(t) if in_paragraph => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak if in_paragraph => text.push(' '),
+            _ => {}
+        }
+    }
+
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= EXCERPT_LENGTH {
+        return Some(text.to_string());
+    }
+    let mut short: String = text.chars().take(EXCERPT_LENGTH).collect();
+    short.push('…');
+    Some(short)
+}
+
+/// Headings in document order, with unique anchors derived from their text
+fn headings(events: &[Event]) -> Vec<TocItem> {
+    let mut items = vec![];
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut current: Option<(u32, String)> = None;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading(level, ..)) => current = Some((*level as u32, String::new())),
+            Event::Text(t) | Event::Code(t) => {
+                if let Some((_, title)) = current.as_mut() {
+                    title.push_str(t);
+                }
+            }
+            Event::End(Tag::Heading(..)) => {
+                if let Some((level, title)) = current.take() {
+                    let base = slugify(&title);
+                    let count = seen.entry(base.clone()).or_insert(0);
+                    let anchor = match *count {
+                        0 => base,
+                        n => format!("{}-{}", base, n),
+                    };
+                    *count += 1;
+                    items.push(TocItem {
+                        level,
+                        anchor,
+                        title: title.trim().to_string(),
+                        children: vec![],
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    items
+}
+
+/// Turn the flat heading list into a tree, each heading owns the deeper ones following it
+fn nest(items: Vec<TocItem>) -> Vec<TocItem> {
+    let mut stack: Vec<TocItem> = vec![];
+    let mut roots = vec![];
+
+    for item in items {
+        while stack.last().is_some_and(|top| top.level >= item.level) {
+            let done = stack.pop().unwrap();
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(done),
+                None => roots.push(done),
+            }
+        }
+        stack.push(item);
+    }
+    while let Some(done) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(done),
+            None => roots.push(done),
+        }
+    }
+
+    roots
+}
+
+/// Lowercase words joined by `-`, CJK characters are kept as they are
+fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        String::from("section")
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Count latin words and CJK characters, each CJK character reads as a word
+fn count_words(text: &str) -> (usize, usize) {
+    let mut words = 0;
+    let mut cjk_chars = 0;
+    let mut in_word = false;
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            cjk_chars += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                words += 1;
+            }
+            in_word = true;
+        } else if c != '\'' && c != '-' {
+            in_word = false;
+        }
+    }
+
+    (words, cjk_chars)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{4e00}'..='\u{9fff}'     // CJK unified ideographs
+        | '\u{3400}'..='\u{4dbf}'   // extension A
+        | '\u{f900}'..='\u{faff}'   // compatibility ideographs
+        | '\u{3040}'..='\u{30ff}'   // hiragana and katakana
+        | '\u{ac00}'..='\u{d7af}'   // hangul syllables
+        | '\u{20000}'..='\u{2a6df}' // extension B
+    )
+}
+
+fn syntaxes() -> &'static SyntaxSet {
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// Highlight a fenced code block with the syntax named by the fence, or guessed from
+/// its first line, e.g. a shebang. Tokens are marked with classes, no inline style.
+fn highlight(lang: &str, source: &str) -> String {
+    let syntaxes = syntaxes();
+    // the fence may carry more than the language, e.g. "rust,ignore"
+    let token = lang.split([',', ' ']).next().unwrap_or_default();
+    let (syntax, name): (&SyntaxReference, &str) = match syntaxes.find_syntax_by_token(token) {
+        Some(syntax) => (syntax, token),
+        None => {
+            let syntax = syntaxes
+                .find_syntax_by_first_line(source)
+                .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+            let name = syntax
+                .file_extensions
+                .first()
+                .map_or("text", String::as_str);
+            (syntax, name)
+        }
+    };
+
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, CLASS_STYLE);
+    for line in LinesWithEndings::from(source) {
+        if generator
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            // fall back to plain escaped code
+            let mut escaped = String::new();
+            html::push_html(&mut escaped, std::iter::once(Event::Text(source.into())));
+            return format!("<pre><code>{}</code></pre>", escaped);
+        }
+    }
+
+    // `hl-code` carries the theme's background and foreground colors
+    format!(
+        "<pre class=\"highlight hl-code\"><code class=\"language-{}\">{}</code></pre>\n",
+        name.to_lowercase(),
+        generator.finalize()
+    )
+}
+
+/// Stylesheet of a highlighting theme for the classes emitted in rendered code blocks,
+/// `None` when there is no such theme
+pub fn highlight_css(theme: &str) -> Option<String> {
+    let themes = ThemeSet::load_defaults();
+    let theme = themes.themes.get(theme)?;
+    css_for_theme_with_class_style(theme, CLASS_STYLE).ok()
+}
+
+/// Names of the available highlighting themes
+pub fn highlight_themes() -> Vec<String> {
+    ThemeSet::load_defaults().themes.into_keys().collect()
+}
+
+/// Strip scripts, event handlers and any markup not produced by markdown
+fn sanitize(unsafe_html: &str) -> String {
+    Builder::default()
+        // headings and footnotes link to ids, code blocks carry their language as class
+        .add_generic_attributes(["id", "class"])
+        // task list checkboxes
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        // table column alignment
+        .add_tag_attributes("th", ["style"])
+        .add_tag_attributes("td", ["style"])
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            (_, "style") if !is_text_align(value) => None,
+            ("input", "type") if value != "checkbox" => None,
+            _ => Some(value.into()),
+        })
+        .clean(unsafe_html)
+        .to_string()
+}
+
+fn is_text_align(style: &str) -> bool {
+    matches!(
+        style,
+        "text-align: left" | "text-align: center" | "text-align: right"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_and_event_handlers_are_removed() {
+        let html =
+            render("hello <script>alert(1)</script>\n\n<img src=\"a.png\" onerror=\"alert(1)\">");
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onerror"));
+        assert!(html.contains("<img src=\"a.png\">"));
+    }
+
+    #[test]
+    fn javascript_links_are_removed() {
+        let html = render("[click](javascript:alert(1))");
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn task_lists_and_table_alignment_are_kept() {
+        let html = render("- [x] done\n\n| a | b |\n|:-:|---|\n| 1 | 2 |");
+        assert!(html.contains("<input disabled=\"\" type=\"checkbox\" checked=\"\">"));
+        assert!(html.contains("style=\"text-align: center\""));
+    }
+
+    fn item(level: u32, title: &str) -> TocItem {
+        TocItem {
+            level,
+            anchor: slugify(title),
+            title: title.to_string(),
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn anchors_are_lowercase_words() {
+        assert_eq!(slugify("Hello, World"), "hello-world");
+        assert_eq!(
+            slugify("  snake_case -- and  spaces "),
+            "snake-case-and-spaces"
+        );
+        assert_eq!(slugify("安装 Rust"), "安装-rust");
+        assert_eq!(slugify("!?"), "section");
+    }
+
+    #[test]
+    fn repeated_headings_get_unique_anchors() {
+        let outline = outline("## Usage\n\n## Usage\n\n## Usage");
+        let anchors: Vec<&str> = outline.toc.iter().map(|h| h.anchor.as_str()).collect();
+        assert_eq!(anchors, ["usage", "usage-1", "usage-2"]);
+    }
+
+    #[test]
+    fn headings_own_the_deeper_ones_following_them() {
+        let toc = nest(vec![
+            item(2, "a"),
+            item(3, "a1"),
+            item(4, "a1x"),
+            item(3, "a2"),
+            item(2, "b"),
+            item(4, "b1"),
+        ]);
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].children.len(), 2);
+        assert_eq!(toc[0].children[0].children[0].title, "a1x");
+        assert_eq!(toc[0].children[1].title, "a2");
+        assert_eq!(toc[1].children[0].title, "b1");
+    }
+
+    #[test]
+    fn cjk_characters_count_as_words() {
+        assert_eq!(count_words("It's a well-known fact"), (4, 0));
+        assert_eq!(count_words("学习 Rust 语言"), (1, 4));
+        assert_eq!(count_words("ひらがな와한글"), (0, 7));
+        assert!(is_cjk('漢'));
+        assert!(!is_cjk('a'));
+    }
+
+    #[test]
+    fn reading_time_is_at_least_a_minute() {
+        assert_eq!(outline("short").reading_time, 1);
+        assert_eq!(outline(&"word ".repeat(401)).reading_time, 3);
+        assert_eq!(outline(&"字".repeat(600)).reading_time, 2);
+    }
+
+    #[test]
+    fn excerpts_take_the_first_paragraph() {
+        let content = "# Title\n\n---\n\nFirst *paragraph*\nwith `code`.\n\nSecond.";
+        assert_eq!(
+            excerpt(content).as_deref(),
+            Some("First paragraph with code.")
+        );
+        assert_eq!(excerpt("# Only a title"), None);
+    }
+
+    #[test]
+    fn long_excerpts_are_cut_to_200_characters() {
+        let short = excerpt(&"a".repeat(200)).unwrap();
+        assert_eq!(short, "a".repeat(200));
+
+        let long = excerpt(&"中".repeat(300)).unwrap();
+        assert_eq!(long.chars().count(), 201);
+        assert!(long.ends_with("中…"));
+    }
+}
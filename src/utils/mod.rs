@@ -1,3 +1,8 @@
 pub mod avatar;
+pub mod client;
+pub mod diff;
 pub mod hash;
 pub mod jwt;
+pub mod markdown;
+pub mod permission;
+pub mod throttle;
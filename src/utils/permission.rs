@@ -0,0 +1,85 @@
+use std::{marker::PhantomData, ops::Deref, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+
+use crate::{
+    errors::{AuthError, Error},
+    models::role::{Permission, Role},
+    router::AppState,
+    utils::jwt::Claims,
+};
+
+/// Marker type naming the permission a route requires, see [`RequirePermission`]
+pub trait PermissionMarker {
+    const PERMISSION: Permission;
+}
+
+macro_rules! permission_marker {
+    ($($name:ident),*) => {
+        $(
+            #[allow(dead_code)]
+            pub struct $name;
+
+            impl PermissionMarker for $name {
+                const PERMISSION: Permission = Permission::$name;
+            }
+        )*
+    };
+}
+
+permission_marker!(Follow, Comment, Write, Moderate, Admin);
+
+/// Extractor for routes which require the caller's role to grant permission `P`,
+/// e.g. `RequirePermission<Write>`. Rejects with `Error::Forbidden` otherwise.
+pub struct RequirePermission<P: PermissionMarker> {
+    pub claims: Claims,
+    pub role: Role,
+    marker: PhantomData<P>,
+}
+
+impl<P: PermissionMarker> RequirePermission<P> {
+    /// Check an additional permission on the caller's role
+    pub fn has_permission(&self, perm: Permission) -> bool {
+        self.role.has_permission(perm)
+    }
+}
+
+impl<P: PermissionMarker> Deref for RequirePermission<P> {
+    type Target = Claims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.claims
+    }
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    Arc<AppState>: FromRef<S>,
+    S: Send + Sync,
+    P: PermissionMarker,
+{
+    type Rejection = Error;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+
+        let state = Arc::<AppState>::from_ref(state);
+        let role = Role::find_by_user_id(&state.pool, claims.user.id)
+            .await?
+            .ok_or(Error::Auth(AuthError::InvalidToken))?;
+
+        if !role.has_permission(P::PERMISSION) {
+            return Err(Error::Forbidden);
+        }
+
+        Ok(Self {
+            claims,
+            role,
+            marker: PhantomData,
+        })
+    }
+}
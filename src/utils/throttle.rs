@@ -0,0 +1,77 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Counts failed attempts per key, e.g. guesses of a password. A key reaching
+/// `max_attempts` is blocked until `window` has passed since its first failure.
+pub struct Throttle<K> {
+    max_attempts: u32,
+    window: Duration,
+    // failures and the time of the first one, per key
+    failures: Mutex<HashMap<K, (u32, Instant)>>,
+}
+
+impl<K: Eq + Hash> Throttle<K> {
+    pub fn new(max_attempts: u32, window: Duration) -> Self {
+        Self {
+            max_attempts,
+            window,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_blocked(&self, key: &K) -> bool {
+        let failures = self.failures.lock().unwrap();
+        failures.get(key).is_some_and(|(count, since)| {
+            *count >= self.max_attempts && since.elapsed() < self.window
+        })
+    }
+
+    pub fn fail(&self, key: K) {
+        let mut failures = self.failures.lock().unwrap();
+        let window = self.window;
+        failures.retain(|_, (_, since)| since.elapsed() < window);
+        failures.entry(key).or_insert((0, Instant::now())).0 += 1;
+    }
+
+    pub fn reset(&self, key: &K) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_blocked_after_max_attempts() {
+        let throttle = Throttle::new(3, Duration::from_secs(60));
+        throttle.fail("a");
+        throttle.fail("a");
+        assert!(!throttle.is_blocked(&"a"));
+        throttle.fail("a");
+        assert!(throttle.is_blocked(&"a"));
+        assert!(!throttle.is_blocked(&"b"));
+    }
+
+    #[test]
+    fn reset_unblocks_a_key() {
+        let throttle = Throttle::new(1, Duration::from_secs(60));
+        throttle.fail("a");
+        assert!(throttle.is_blocked(&"a"));
+        throttle.reset(&"a");
+        assert!(!throttle.is_blocked(&"a"));
+    }
+
+    #[test]
+    fn keys_are_unblocked_after_the_window() {
+        let throttle = Throttle::new(1, Duration::from_millis(10));
+        throttle.fail("a");
+        assert!(throttle.is_blocked(&"a"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!throttle.is_blocked(&"a"));
+    }
+}
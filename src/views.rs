@@ -0,0 +1,167 @@
+//! Counting of article views. A reader is counted once per article within a time
+//! window, views are buffered in memory and written to the database in batches.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDate;
+use sqlx::MySqlPool;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+use crate::{errors::AppResult, models::article_view::ArticleView};
+
+pub struct ViewCounter {
+    // a reader coming back within the window is not counted again
+    window: Duration,
+    // readers remembered at most, so anonymous clients can not grow the buffer forever
+    max_readers: usize,
+    buffer: Mutex<Buffer>,
+}
+
+#[derive(Default)]
+struct Buffer {
+    // when each reader was last counted, per article
+    seen: HashMap<(i32, String), Instant>,
+    // the same, oldest first. A reader counted again is in it twice until the older entry
+    // is forgotten.
+    order: VecDeque<((i32, String), Instant)>,
+    // views not written yet, per article and day
+    pending: HashMap<(i32, NaiveDate), i32>,
+}
+
+impl Buffer {
+    /// Forget the readers counted before the window, then the oldest ones beyond `max`
+    fn forget(&mut self, window: Duration, max: usize) {
+        while let Some((_, at)) = self.order.front() {
+            if at.elapsed() < window && self.order.len() <= max {
+                break;
+            }
+            let (key, at) = self.order.pop_front().unwrap();
+            if self.seen.get(&key) == Some(&at) {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+impl ViewCounter {
+    pub fn new(window: Duration, max_readers: usize) -> Self {
+        Self {
+            window,
+            max_readers,
+            buffer: Mutex::new(Buffer::default()),
+        }
+    }
+
+    /// Count a view of the article by `viewer`, unless it was counted within the window
+    pub fn record(&self, article_id: i32, viewer: String) {
+        let now = Instant::now();
+        let mut buffer = self.buffer.lock().unwrap();
+        let key = (article_id, viewer);
+        if let Some(at) = buffer.seen.get(&key) {
+            if now.duration_since(*at) < self.window {
+                return;
+            }
+        }
+
+        buffer.seen.insert(key.clone(), now);
+        buffer.order.push_back((key, now));
+        buffer.forget(self.window, self.max_readers);
+        let today = chrono::Utc::now().date_naive();
+        *buffer.pending.entry((article_id, today)).or_default() += 1;
+    }
+
+    /// Write the buffered views to the database. On failure they go back to the buffer,
+    /// to be written by the next flush.
+    pub async fn flush(&self, pool: &MySqlPool) -> AppResult<()> {
+        let pending = {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.forget(self.window, self.max_readers);
+            std::mem::take(&mut buffer.pending)
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        if let Err(e) = ArticleView::add(pool, &pending).await {
+            let mut buffer = self.buffer.lock().unwrap();
+            for (key, views) in pending {
+                *buffer.pending.entry(key).or_default() += views;
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+/// Flush the buffered views every `interval` seconds, forever
+pub fn spawn(counter: Arc<ViewCounter>, pool: MySqlPool, interval: u64) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = counter.flush(&pool).await {
+                tracing::error!("flush article views failed: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn views(counter: &ViewCounter, article_id: i32) -> i32 {
+        let buffer = counter.buffer.lock().unwrap();
+        buffer
+            .pending
+            .iter()
+            .filter(|((id, _), _)| *id == article_id)
+            .map(|(_, views)| views)
+            .sum()
+    }
+
+    #[test]
+    fn readers_are_counted_once_within_the_window() {
+        let counter = ViewCounter::new(Duration::from_secs(60), 10);
+        counter.record(1, String::from("a"));
+        counter.record(1, String::from("a"));
+        counter.record(1, String::from("b"));
+        counter.record(2, String::from("a"));
+        assert_eq!(views(&counter, 1), 2);
+        assert_eq!(views(&counter, 2), 1);
+    }
+
+    #[test]
+    fn readers_are_counted_again_after_the_window() {
+        let counter = ViewCounter::new(Duration::from_millis(10), 10);
+        counter.record(1, String::from("a"));
+        std::thread::sleep(Duration::from_millis(20));
+        counter.record(1, String::from("a"));
+        assert_eq!(views(&counter, 1), 2);
+        assert_eq!(counter.buffer.lock().unwrap().order.len(), 1);
+    }
+
+    #[test]
+    fn the_oldest_readers_are_forgotten_beyond_the_limit() {
+        let counter = ViewCounter::new(Duration::from_secs(60), 3);
+        for viewer in ["a", "b", "c", "d", "e"] {
+            counter.record(1, String::from(viewer));
+        }
+        {
+            let buffer = counter.buffer.lock().unwrap();
+            assert_eq!(buffer.seen.len(), 3);
+            assert_eq!(buffer.order.len(), 3);
+        }
+
+        // "a" was forgotten, "e" was not
+        counter.record(1, String::from("a"));
+        counter.record(1, String::from("e"));
+        assert_eq!(views(&counter, 1), 6);
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use sqlx::MySqlPool;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+use crate::{
+    errors::AppResult,
+    models::{article::Article, category::Category, tag::Tag, user::User},
+};
+
+// rows handled per job and tick, the rest waits for the next tick
+const BATCH_SIZE: i64 = 100;
+
+/// Run the background jobs inside the current process, e.g. next to the http server
+pub fn spawn(pool: MySqlPool, interval: u64, trash_retention: u32) -> JoinHandle<()> {
+    tokio::spawn(run(pool, interval, trash_retention))
+}
+
+/// Run the background jobs every `interval` seconds, forever. Several workers may run
+/// against the same database, rows are claimed with row-level locks.
+/// Deleted items are purged `trash_retention` days after they were trashed.
+pub async fn run(pool: MySqlPool, interval: u64, trash_retention: u32) {
+    tracing::info!("Worker started, running jobs every {}s", interval);
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = publish_scheduled(&pool).await {
+            tracing::error!("publish scheduled articles failed: {}", e);
+        }
+        if let Err(e) = unpublish_expired(&pool).await {
+            tracing::error!("unpublish expired articles failed: {}", e);
+        }
+        if trash_retention > 0 {
+            if let Err(e) = purge_trash(&pool, trash_retention).await {
+                tracing::error!("purge trash failed: {}", e);
+            }
+        }
+    }
+}
+
+async fn publish_scheduled(pool: &MySqlPool) -> AppResult<()> {
+    let now = chrono::Utc::now().naive_utc();
+    let ids = Article::publish_due(pool, now, BATCH_SIZE).await?;
+    if !ids.is_empty() {
+        tracing::info!("published scheduled articles {:?}", ids);
+    }
+    Ok(())
+}
+
+async fn unpublish_expired(pool: &MySqlPool) -> AppResult<()> {
+    let now = chrono::Utc::now().naive_utc();
+    let ids = Article::archive_expired(pool, now, BATCH_SIZE).await?;
+    if !ids.is_empty() {
+        tracing::info!("archived expired articles {:?}", ids);
+    }
+    Ok(())
+}
+
+/// Articles go first, their authors and categories can only be purged once they are gone
+async fn purge_trash(pool: &MySqlPool, retention: u32) -> AppResult<()> {
+    let before = (chrono::Utc::now() - chrono::Duration::days(retention as i64)).naive_utc();
+
+    let articles = Article::purge(pool, before, BATCH_SIZE).await?;
+    let tags = Tag::purge(pool, before, BATCH_SIZE).await?;
+    let categories = Category::purge(pool, before, BATCH_SIZE).await?;
+    let users = User::purge(pool, before, BATCH_SIZE).await?;
+    if articles + tags + categories + users > 0 {
+        tracing::info!(
+            "purged {} articles, {} tags, {} categories and {} users from the trash",
+            articles,
+            tags,
+            categories,
+            users
+        );
+    }
+    Ok(())
+}